@@ -0,0 +1,527 @@
+// Software synthesizer for the sound effects and the music.
+//
+// The samples are computed in integers, so the output is bit for bit
+// the same on every platform. Samples are signed 16-bit mono PCM
+// written into a ring buffer that the frontend drains at its own pace.
+
+pub const SAMPLE_RATE: u32 = 22050;
+pub const RING_CAPACITY: usize = 8192;
+const VOICES_CAPACITY: usize = 8;
+// Envelope and volume gains are fixed point with ONE meaning 1.0
+const ONE: i64 = 1 << 16;
+const AMPLITUDE: i64 = i16::MAX as i64;
+const NOISE_SEED: u32 = 0x12345678;
+const MUSIC_CHANNELS: usize = 4;
+const PATTERN_ROWS: usize = 16;
+// Characters a cell takes in the pattern text including the separator
+const PATTERN_CELL_WIDTH: usize = 4;
+// Frequencies of the 4th octave starting from C
+const NOTE_FREQUENCIES: [f32; 12] = [
+    261.63, 277.18, 293.66, 311.13, 329.63, 349.23,
+    369.99, 392.00, 415.30, 440.00, 466.16, 493.88,
+];
+
+#[derive(Clone, Copy)]
+pub enum Waveform {
+    Square,
+    Saw,
+    Triangle,
+    Noise,
+}
+
+// Times are in seconds, frequencies in Hz, levels from 0.0 to 1.0
+#[derive(Clone, Copy)]
+pub struct Sound {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    // The frequency slides linearly to this one by the end of the sound
+    pub sweep: f32,
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub hold: f32,
+    pub release: f32,
+    pub volume: f32,
+}
+
+pub const SHOOT: Sound = Sound {
+    waveform: Waveform::Square,
+    frequency: 880.0,
+    sweep: 220.0,
+    attack: 0.0,
+    decay: 0.05,
+    sustain: 0.3,
+    hold: 0.0,
+    release: 0.05,
+    volume: 0.15,
+};
+
+pub const EXPLOSION: Sound = Sound {
+    waveform: Waveform::Noise,
+    frequency: 4000.0,
+    sweep: 200.0,
+    attack: 0.0,
+    decay: 0.1,
+    sustain: 0.5,
+    hold: 0.1,
+    release: 0.3,
+    volume: 0.5,
+};
+
+pub const HIT: Sound = Sound {
+    waveform: Waveform::Saw,
+    frequency: 300.0,
+    sweep: 60.0,
+    attack: 0.0,
+    decay: 0.1,
+    sustain: 0.6,
+    hold: 0.2,
+    release: 0.2,
+    volume: 0.6,
+};
+
+pub const PICKUP: Sound = Sound {
+    waveform: Waveform::Square,
+    frequency: 440.0,
+    sweep: 1760.0,
+    attack: 0.01,
+    decay: 0.05,
+    sustain: 0.7,
+    hold: 0.1,
+    release: 0.1,
+    volume: 0.3,
+};
+
+// A channel of the song always plays the same instrument. Channels
+// above the current intensity of the music stay silent.
+#[derive(Clone, Copy)]
+struct Instrument {
+    sound: Sound,
+    intensity: u32,
+}
+
+// Rows of a channel are cells like `C-4` or `F#2` for the notes
+// and `...` for keeping the previous one, separated by spaces
+type Pattern = [&'static [u8]; MUSIC_CHANNELS];
+
+struct Song {
+    beats_per_minute: u32,
+    rows_per_beat: u32,
+    instruments: [Instrument; MUSIC_CHANNELS],
+    patterns: &'static [Pattern],
+    // Indices of the patterns in the order they are played
+    order: &'static [usize],
+}
+
+const SONG: Song = Song {
+    beats_per_minute: 120,
+    rows_per_beat: 4,
+    instruments: [
+        // Bass
+        Instrument {
+            sound: Sound {
+                waveform: Waveform::Triangle,
+                frequency: 0.0,
+                sweep: 0.0,
+                attack: 0.01,
+                decay: 0.1,
+                sustain: 0.8,
+                hold: 0.15,
+                release: 0.1,
+                volume: 0.25,
+            },
+            intensity: 0,
+        },
+        // Lead
+        Instrument {
+            sound: Sound {
+                waveform: Waveform::Square,
+                frequency: 0.0,
+                sweep: 0.0,
+                attack: 0.01,
+                decay: 0.1,
+                sustain: 0.5,
+                hold: 0.2,
+                release: 0.15,
+                volume: 0.08,
+            },
+            intensity: 0,
+        },
+        // Drums
+        Instrument {
+            sound: Sound {
+                waveform: Waveform::Noise,
+                frequency: 0.0,
+                sweep: 0.0,
+                attack: 0.0,
+                decay: 0.05,
+                sustain: 0.2,
+                hold: 0.0,
+                release: 0.05,
+                volume: 0.15,
+            },
+            intensity: 1,
+        },
+        // Arpeggio
+        Instrument {
+            sound: Sound {
+                waveform: Waveform::Saw,
+                frequency: 0.0,
+                sweep: 0.0,
+                attack: 0.0,
+                decay: 0.05,
+                sustain: 0.4,
+                hold: 0.0,
+                release: 0.05,
+                volume: 0.08,
+            },
+            intensity: 2,
+        },
+    ],
+    patterns: &[
+        [
+            b"A-2 ... A-2 ... A-2 ... A-2 ... F-2 ... F-2 ... G-2 ... G-2 ...",
+            b"A-4 ... C-5 ... E-5 ... D-5 ... C-5 ... ... ... B-4 ... G-4 ...",
+            b"C-8 ... C-4 ... C-8 ... C-4 ... C-8 ... C-4 ... C-8 ... C-4 C-4",
+            b"A-5 C-6 E-6 C-6 A-5 C-6 E-6 C-6 F-5 A-5 C-6 A-5 G-5 B-5 D-6 B-5",
+        ],
+        [
+            b"D-2 ... D-2 ... D-2 ... D-2 ... E-2 ... E-2 ... E-2 ... E-2 ...",
+            b"F-5 ... E-5 ... D-5 ... A-4 ... B-4 ... ... ... G#4 ... E-4 ...",
+            b"C-8 ... C-4 ... C-8 ... C-4 ... C-8 ... C-4 ... C-8 C-4 C-4 C-4",
+            b"D-5 F-5 A-5 F-5 D-5 F-5 A-5 F-5 E-5 G#5 B-5 G#5 E-5 G#5 B-5 G#5",
+        ],
+    ],
+    order: &[0, 0, 1, 0, 0, 1, 1],
+};
+
+// Parses a cell like `C#4` into the frequency of the note
+fn note_frequency(cell: &[u8]) -> Option<f32> {
+    let (letter, accidental, octave) = match cell {
+        [letter, accidental, octave] => (*letter, *accidental, *octave),
+        _ => return None,
+    };
+    let natural = match letter {
+        b'C' => 0,
+        b'D' => 2,
+        b'E' => 4,
+        b'F' => 5,
+        b'G' => 7,
+        b'A' => 9,
+        b'B' => 11,
+        _ => return None,
+    };
+    let semitone = match accidental {
+        b'-' => natural,
+        b'#' => natural + 1,
+        _ => return None,
+    };
+    if !octave.is_ascii_digit() {
+        return None;
+    }
+
+    let mut frequency = *NOTE_FREQUENCIES.get(semitone)?;
+    for _ in (octave - b'0')..4 {
+        frequency /= 2.0;
+    }
+    for _ in 4..(octave - b'0') {
+        frequency *= 2.0;
+    }
+    Some(frequency)
+}
+
+fn samples(seconds: f32) -> u32 {
+    (seconds * SAMPLE_RATE as f32) as u32
+}
+
+// Phase increment per sample for the frequency, where the full
+// period is 2^32
+fn increment(frequency: f32) -> i64 {
+    (frequency as f64 * (1u64 << 32) as f64 / SAMPLE_RATE as f64) as i64
+}
+
+#[derive(Clone, Copy)]
+struct Voice {
+    waveform: Waveform,
+    phase: u32,
+    increment: i64,
+    sweep: i64,
+    attack: u32,
+    decay: u32,
+    sustain: i64,
+    hold: u32,
+    release: u32,
+    volume: i64,
+    age: u32,
+    noise: u32,
+    noise_value: i64,
+    alive: bool,
+}
+
+impl Voice {
+    const fn dead() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            phase: 0,
+            increment: 0,
+            sweep: 0,
+            attack: 0,
+            decay: 0,
+            sustain: 0,
+            hold: 0,
+            release: 0,
+            volume: 0,
+            age: 0,
+            noise: NOISE_SEED,
+            noise_value: 0,
+            alive: false,
+        }
+    }
+
+    fn start(&mut self, sound: &Sound) {
+        self.waveform = sound.waveform;
+        self.phase = 0;
+        self.attack = samples(sound.attack);
+        self.decay = samples(sound.decay);
+        self.sustain = (sound.sustain * ONE as f32) as i64;
+        self.hold = samples(sound.hold);
+        self.release = samples(sound.release);
+        self.volume = (sound.volume * ONE as f32) as i64;
+        self.age = 0;
+        self.alive = true;
+
+        let length = (self.attack + self.decay + self.hold + self.release).max(1) as i64;
+        self.increment = increment(sound.frequency);
+        self.sweep = (increment(sound.sweep) - self.increment) / length;
+    }
+
+    // Attack-Decay-Sustain-Release gain of the current sample
+    fn envelope(&self) -> Option<i64> {
+        let mut age = self.age;
+        if age < self.attack {
+            return Some(age as i64 * ONE / self.attack as i64);
+        }
+        age -= self.attack;
+        if age < self.decay {
+            return Some(ONE - (ONE - self.sustain) * age as i64 / self.decay as i64);
+        }
+        age -= self.decay;
+        if age < self.hold {
+            return Some(self.sustain);
+        }
+        age -= self.hold;
+        if age < self.release {
+            return Some(self.sustain - self.sustain * age as i64 / self.release as i64);
+        }
+        None
+    }
+
+    fn oscillator(&mut self) -> i64 {
+        match self.waveform {
+            Waveform::Square => {
+                if self.phase < 1 << 31 {
+                    AMPLITUDE
+                } else {
+                    -AMPLITUDE
+                }
+            }
+            Waveform::Saw => (self.phase >> 16) as i64 - (1 << 15),
+            Waveform::Triangle => {
+                let ramp = (self.phase >> 15) as i64;
+                if ramp < 1 << 16 {
+                    ramp - (1 << 15)
+                } else {
+                    (3 << 15) - ramp
+                }
+            }
+            Waveform::Noise => self.noise_value,
+        }
+    }
+
+    fn next_sample(&mut self) -> i64 {
+        let gain = match self.envelope() {
+            Some(gain) => gain,
+            None => {
+                self.alive = false;
+                return 0;
+            }
+        };
+        let sample = self.oscillator() * gain / ONE * self.volume / ONE;
+
+        let (phase, wrapped) = self.phase.overflowing_add(self.increment as u32);
+        self.phase = phase;
+        // Noise picks a new random level once per period, so its
+        // frequency controls how rough it sounds
+        if wrapped {
+            self.noise ^= self.noise << 13;
+            self.noise ^= self.noise >> 17;
+            self.noise ^= self.noise << 5;
+            self.noise_value = (self.noise >> 16) as i64 - (1 << 15);
+        }
+        self.increment = (self.increment + self.sweep).max(0);
+        self.age += 1;
+        sample
+    }
+}
+
+struct Music {
+    voices: [Voice; MUSIC_CHANNELS],
+    order: usize,
+    row: usize,
+    // Samples left until the next row
+    countdown: u32,
+    // In percents of the song's own tempo
+    tempo: u32,
+    intensity: u32,
+}
+
+impl Music {
+    const fn new() -> Self {
+        Self {
+            voices: [Voice::dead(); MUSIC_CHANNELS],
+            order: 0,
+            row: 0,
+            countdown: 0,
+            tempo: 100,
+            intensity: 0,
+        }
+    }
+
+    fn play_row(&mut self) {
+        let pattern = SONG
+            .order
+            .get(self.order)
+            .and_then(|index| SONG.patterns.get(*index));
+        if let Some(pattern) = pattern {
+            for ((voice, instrument), channel) in self.voices.iter_mut().zip(SONG.instruments.iter()).zip(pattern.iter()) {
+                let start = self.row * PATTERN_CELL_WIDTH;
+                let frequency = channel
+                    .get(start..start + PATTERN_CELL_WIDTH - 1)
+                    .and_then(note_frequency);
+                if let Some(frequency) = frequency {
+                    if instrument.intensity <= self.intensity {
+                        voice.start(&Sound {
+                            frequency,
+                            sweep: frequency,
+                            ..instrument.sound
+                        });
+                    }
+                }
+            }
+        }
+
+        self.row += 1;
+        if self.row >= PATTERN_ROWS {
+            self.row = 0;
+            self.order = (self.order + 1) % SONG.order.len();
+        }
+    }
+
+    fn next_sample(&mut self) -> i64 {
+        if self.countdown == 0 {
+            self.play_row();
+            // At least a sample per row however fast the tempo is
+            self.countdown = (SAMPLE_RATE * 60 * 100
+                / (SONG.beats_per_minute * SONG.rows_per_beat * self.tempo.max(1))).max(1);
+        }
+        self.countdown -= 1;
+
+        let mut mix = 0;
+        for voice in self.voices.iter_mut() {
+            if voice.alive {
+                mix += voice.next_sample();
+            }
+        }
+        mix
+    }
+}
+
+pub struct Audio {
+    voices: [Voice; VOICES_CAPACITY],
+    music: Music,
+    // In percents
+    volume: i64,
+    ring: [i16; RING_CAPACITY],
+    position: usize,
+    // Fraction of a sample left over from the previous render
+    pending: f32,
+}
+
+impl Audio {
+    pub const fn new() -> Self {
+        Self {
+            voices: [Voice::dead(); VOICES_CAPACITY],
+            music: Music::new(),
+            volume: 100,
+            ring: [0; RING_CAPACITY],
+            position: 0,
+            pending: 0.0,
+        }
+    }
+
+    pub fn play(&mut self, sound: &Sound) {
+        // When all the voices are busy, the oldest one is cut off
+        let mut chosen = 0;
+        for (index, voice) in self.voices.iter().enumerate() {
+            if !voice.alive {
+                chosen = index;
+                break;
+            }
+            if self.voices.get(chosen).map(|oldest| voice.age > oldest.age).unwrap_or(false) {
+                chosen = index;
+            }
+        }
+        if let Some(voice) = self.voices.get_mut(chosen) {
+            voice.start(sound);
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: u32) {
+        self.volume = volume as i64;
+    }
+
+    // Speed of the music in percents of its normal tempo
+    pub fn set_music_tempo(&mut self, tempo: u32) {
+        self.music.tempo = tempo;
+    }
+
+    // The higher the intensity the more channels of the music play
+    pub fn set_music_intensity(&mut self, intensity: u32) {
+        self.music.intensity = intensity;
+    }
+
+    pub fn next_sample(&mut self) -> i16 {
+        let mut mix = self.music.next_sample();
+        for voice in self.voices.iter_mut() {
+            if voice.alive {
+                mix += voice.next_sample();
+            }
+        }
+        (mix * self.volume / 100).clamp(i16::MIN as i64, i16::MAX as i64) as i16
+    }
+
+    // Appends dt seconds worth of samples to the ring buffer
+    pub fn render(&mut self, dt: f32) {
+        self.pending += dt * SAMPLE_RATE as f32;
+        // A full lap would bring the position back to where it was and
+        // the frontend would see no new samples at all
+        let count = (self.pending as usize).min(RING_CAPACITY - 1);
+        self.pending -= self.pending as usize as f32;
+        for _ in 0..count {
+            let sample = self.next_sample();
+            if let Some(sample_ref) = self.ring.get_mut(self.position) {
+                *sample_ref = sample;
+            }
+            self.position = (self.position + 1) % RING_CAPACITY;
+        }
+    }
+
+    pub fn ring(&self) -> &[i16; RING_CAPACITY] {
+        &self.ring
+    }
+
+    // Index in the ring where the next sample will be written
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
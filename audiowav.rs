@@ -0,0 +1,214 @@
+// Renders the audio of the game without the game. Plays scripted
+// events through the same mixer and ring buffer the game uses and
+// writes the result into a WAV file or checks it against the
+// reference checksums (see audio.sums).
+//
+// Usage: audiowav <scenario> <output.wav>
+//        audiowav --check <audio.sums>
+//        audiowav --checksums
+
+mod audio;
+
+use std::env;
+use std::fs;
+use std::process::exit;
+
+// Same frame rate as the browser, so the ring buffer is drained the
+// same way
+const FRAME_DT: f32 = 1.0 / 60.0;
+
+enum Action {
+    Play(&'static audio::Sound),
+    Tempo(u32),
+    Intensity(u32),
+    Volume(u32),
+}
+
+struct Event {
+    frame: usize,
+    action: Action,
+}
+
+struct Scenario {
+    name: &'static str,
+    frames: usize,
+    events: &'static [Event],
+}
+
+const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "sfx",
+        frames: 180,
+        events: &[
+            Event { frame: 0, action: Action::Play(&audio::SHOOT) },
+            Event { frame: 15, action: Action::Play(&audio::SHOOT) },
+            Event { frame: 30, action: Action::Play(&audio::EXPLOSION) },
+            Event { frame: 90, action: Action::Play(&audio::HIT) },
+            Event { frame: 140, action: Action::Play(&audio::PICKUP) },
+        ],
+    },
+    Scenario {
+        name: "music",
+        frames: 900,
+        events: &[
+            Event { frame: 0, action: Action::Intensity(1) },
+        ],
+    },
+    Scenario {
+        name: "boss",
+        frames: 600,
+        events: &[
+            Event { frame: 0, action: Action::Intensity(2) },
+            Event { frame: 0, action: Action::Tempo(115) },
+            Event { frame: 60, action: Action::Play(&audio::EXPLOSION) },
+            Event { frame: 300, action: Action::Play(&audio::HIT) },
+            Event { frame: 500, action: Action::Play(&audio::EXPLOSION) },
+        ],
+    },
+    Scenario {
+        name: "low-health",
+        frames: 600,
+        events: &[
+            Event { frame: 0, action: Action::Intensity(1) },
+            Event { frame: 0, action: Action::Play(&audio::HIT) },
+            Event { frame: 0, action: Action::Tempo(125) },
+            Event { frame: 200, action: Action::Play(&audio::PICKUP) },
+        ],
+    },
+    Scenario {
+        name: "volume",
+        frames: 300,
+        events: &[
+            Event { frame: 0, action: Action::Intensity(2) },
+            Event { frame: 0, action: Action::Volume(50) },
+            Event { frame: 30, action: Action::Play(&audio::EXPLOSION) },
+            Event { frame: 150, action: Action::Volume(0) },
+            Event { frame: 200, action: Action::Volume(100) },
+        ],
+    },
+];
+
+// Plays the scenario frame by frame collecting the samples from the
+// ring buffer like a frontend would
+fn render(scenario: &Scenario) -> Vec<i16> {
+    let mut audio = audio::Audio::new();
+    let mut samples = Vec::new();
+    let mut read = audio.position();
+    for frame in 0..scenario.frames {
+        for event in scenario.events.iter().filter(|event| event.frame == frame) {
+            match event.action {
+                Action::Play(sound) => audio.play(sound),
+                Action::Tempo(tempo) => audio.set_music_tempo(tempo),
+                Action::Intensity(intensity) => audio.set_music_intensity(intensity),
+                Action::Volume(volume) => audio.set_volume(volume),
+            }
+        }
+
+        audio.render(FRAME_DT);
+        while read != audio.position() {
+            samples.push(audio.ring()[read]);
+            read = (read + 1) % audio::RING_CAPACITY;
+        }
+    }
+    samples
+}
+
+// 64-bit FNV-1a of the little-endian samples
+fn checksum(samples: &[i16]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in samples.iter().flat_map(|sample| sample.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn wav(samples: &[i16]) -> Vec<u8> {
+    let data_size = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+    bytes.extend_from_slice(&audio::SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(audio::SAMPLE_RATE * 2).to_le_bytes()); // bytes per second
+    bytes.extend_from_slice(&2u16.to_le_bytes()); // bytes per sample
+    bytes.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+fn find_scenario(name: &str) -> &'static Scenario {
+    SCENARIOS.iter().find(|scenario| scenario.name == name).unwrap_or_else(|| {
+        let names: Vec<&str> = SCENARIOS.iter().map(|scenario| scenario.name).collect();
+        eprintln!("ERROR: unknown scenario `{}`, expected one of: {}", name, names.join(", "));
+        exit(1);
+    })
+}
+
+// Compares every scenario against the `<name> <checksum>` lines of the
+// file
+fn check(path: &str) {
+    let source = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("ERROR: could not read {}: {}", path, err);
+        exit(1);
+    });
+
+    let mut failed = 0;
+    for scenario in SCENARIOS {
+        let actual = checksum(&render(scenario));
+        let expected = source
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .find(|(name, _)| *name == scenario.name)
+            .and_then(|(_, sum)| u64::from_str_radix(sum.trim(), 16).ok());
+        match expected {
+            Some(expected) if expected == actual => println!("{}: OK", scenario.name),
+            Some(expected) => {
+                println!("{}: FAILED, expected {:016x}, but got {:016x}", scenario.name, expected, actual);
+                failed += 1;
+            }
+            None => {
+                println!("{}: FAILED, no reference checksum in {}", scenario.name, path);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("ERROR: {} of {} scenario(s) failed", failed, SCENARIOS.len());
+        exit(1);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.as_slice() {
+        [_, flag, path] if flag == "--check" => check(path),
+        [_, flag] if flag == "--checksums" => {
+            for scenario in SCENARIOS {
+                println!("{} {:016x}", scenario.name, checksum(&render(scenario)));
+            }
+        }
+        [_, name, output_path] => {
+            let samples = render(find_scenario(name));
+            fs::write(output_path, wav(&samples)).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not write {}: {}", output_path, err);
+                exit(1);
+            });
+        }
+        _ => {
+            eprintln!("Usage: audiowav <scenario> <output.wav>");
+            eprintln!("       audiowav --check <audio.sums>");
+            eprintln!("       audiowav --checksums");
+            exit(1);
+        }
+    }
+}
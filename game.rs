@@ -1,44 +1,380 @@
-#![no_main]
-#![no_std]
+#![cfg_attr(not(test), no_main)]
+#![cfg_attr(not(test), no_std)]
 
+// The tests run on the host with std, which does not bring core into
+// the scope of the 2015 edition by itself
+#[cfg(test)]
+extern crate core;
+
+use core::fmt::{self, Write};
+#[cfg(not(test))]
 use core::panic::PanicInfo;
 
+mod audio;
+mod level;
+mod math;
+
+use math::{cos, sin, sqrt, PI};
+
+#[cfg(not(test))]
+const PANIC_MESSAGE_CAPACITY: usize = 512;
+const LOG_MESSAGE_CAPACITY: usize = 256;
+
+macro_rules! error {
+    ($($arg:tt)*) => { log(LogLevel::Error, format_args!($($arg)*)) }
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => { log(LogLevel::Warn, format_args!($($arg)*)) }
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { log(LogLevel::Info, format_args!($($arg)*)) }
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { log(LogLevel::Debug, format_args!($($arg)*)) }
+}
+
+#[cfg(not(test))]
 #[panic_handler]
-fn panic(_panic: &PanicInfo<'_>) -> ! {
+fn panic(info: &PanicInfo<'_>) -> ! {
+    let mut message = FixedBuffer::<PANIC_MESSAGE_CAPACITY>::new();
+    let _ = write!(message, "{}", info);
+    let bytes = message.as_bytes();
+    unsafe {
+        js_panic(bytes.as_ptr(), bytes.len());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    core::arch::wasm32::unreachable();
+    #[cfg(not(target_arch = "wasm32"))]
     loop {}
 }
 
+extern "C" {
+    // Called with the UTF-8 message of the panic right before the
+    // module traps, so the host can tell the player what happened
+    #[cfg(not(test))]
+    fn js_panic(message: *const u8, length: usize);
+    // Prints the UTF-8 message with the given LogLevel
+    fn js_log(level: u32, message: *const u8, length: usize);
+}
+
+#[derive(Clone, Copy)]
+#[repr(u32)]
+enum LogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+// Messages with a level above this one are not sent to the host
+static mut LOG_LEVEL: u32 = LogLevel::Info as u32;
+
+fn log(level: LogLevel, args: fmt::Arguments<'_>) {
+    if level as u32 <= unsafe { LOG_LEVEL } {
+        let mut message = FixedBuffer::<LOG_MESSAGE_CAPACITY>::new();
+        let _ = message.write_fmt(args);
+        let bytes = message.as_bytes();
+        unsafe {
+            js_log(level as u32, bytes.as_ptr(), bytes.len());
+        }
+    }
+}
+
+// Text formatted without any allocations. Whatever does not fit into
+// the capacity is silently cut off.
+#[derive(Clone, Copy)]
+struct FixedBuffer<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> FixedBuffer<N> {
+    const fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self.bytes.get(0..self.len).unwrap_or(&[])
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn push_byte(&mut self, b: u8) {
+        if let Some(byte_ref) = self.bytes.get_mut(self.len) {
+            *byte_ref = b;
+            self.len += 1;
+        }
+    }
+
+    fn push_bytes(&mut self, bs: &[u8]) {
+        for b in bs {
+            self.push_byte(*b);
+        }
+    }
+
+    // Zero-padded to at least `width` digits
+    fn push_padded(&mut self, x: usize, width: usize) {
+        let _ = write!(self, "{:01$}", x, width);
+    }
+
+    // Minutes and seconds like `03:25`
+    fn push_timer(&mut self, time: Seconds) {
+        let seconds = max(time as i32, 0) as usize;
+        self.push_padded(seconds / 60, 2);
+        self.push_byte(b':');
+        self.push_padded(seconds % 60, 2);
+    }
+
+    // Thousands separated with commas like `1,234,567`
+    fn push_grouped(&mut self, x: usize) {
+        if x >= 1000 {
+            self.push_grouped(x / 1000);
+            self.push_byte(b',');
+            self.push_padded(x % 1000, 3);
+        } else {
+            self.push_usize(x);
+        }
+    }
+
+    fn push_usize(&mut self, mut x: usize) {
+        let saved_len = self.len;
+
+        if x == 0 {
+            self.push_byte(b'0');
+        } else {
+            while x > 0 && self.len < N {
+                self.push_byte((x % 10) as u8 + b'0');
+                x /= 10;
+            }
+
+            if x > 0 {
+                // x does not fit into the buffer rolling back and quitting
+                self.len = saved_len;
+                return;
+            }
+        }
+
+        if let Some(bytes) = self.bytes.get_mut(saved_len .. self.len) {
+            bytes.reverse();
+        }
+    }
+}
+
+impl<const N: usize> Write for FixedBuffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if let Some(byte_ref) = self.bytes.get_mut(self.len) {
+                *byte_ref = byte;
+                self.len += 1;
+            } else {
+                return Err(fmt::Error);
+            }
+        }
+        Ok(())
+    }
+}
+
 const DISPLAY_WIDTH: usize = 800;
 const DISPLAY_HEIGHT: usize = 600;
 const DISPLAY_BACKGROUND: Pixel = Pixel::rgba(0x3F, 0x3F, 0x3F, 0xFF);
 const PLAYER_SIZE: i32 = 80;
 const PLAYER_COLOR: Pixel = Pixel::rgba(0xDF, 0xAF, 0x8F, 0xFF);
 const PLAYER_KILL_REWARD: usize = 100;
-const BULLET_SIZE: i32 = 25;
+const PLAYER_LIVES: usize = 3;
+const PLAYER_MAX_LIVES: usize = 5;
+const PLAYER_INVULNERABILITY_PERIOD: Seconds = 2.0;
+const PLAYER_BLINK_PERIOD: Seconds = 0.1;
+// How fast the player moves with the Left and Right buttons
+const PLAYER_BUTTONS_SPEED: i32 = DISPLAY_WIDTH as i32;
+const LIFE_ICON_SIZE: i32 = 20;
+const LIFE_ICON_PADDING: i32 = 10;
 const BULLET_SPEED: i32 = DISPLAY_HEIGHT as i32 * 2;
 const BULLET_COLOR: Pixel = Pixel::rgba(0xEC, 0xB3, 0xB3, 0xFF);
+const WEAPON_MAX_LEVEL: usize = 3;
+const WEAPON_LABEL_SCALE: i32 = 2;
+const WEAPON_LABEL_Y: i32 = SCORE_LABEL_Y + FONT_CHAR_HEIGHT as i32 * 4 + SCORE_LABEL_PADDING / 2;
+const HOMING_TURN_SPEED: f32 = BULLET_SPEED as f32 * 3.0;
 const ENEMY_SIZE: i32 = 100;
 const ENEMY_COLOR: Pixel = Pixel::rgba(0x7C, 0xB8, 0xBB, 0xFF);
 const ENEMY_SPEED: i32 = DISPLAY_HEIGHT as i32 / 2;
+const ENEMY_HEALTH: i32 = 3;
+const SPLITLING_OFFSET: i32 = 30;
+const ENEMY_BULLET_SIZE: i32 = 14;
+const ENEMY_BULLET_SPEED: i32 = ENEMY_SPEED;
+const ENEMY_BULLET_COLOR: Pixel = Pixel::rgba(0xF0, 0x8C, 0x7C, 0xFF);
+const BOSS_SIZE: i32 = 200;
+const BOSS_HEALTH: i32 = 150;
+const BOSS_COLOR: Pixel = Pixel::rgba(0xCC, 0x93, 0x93, 0xFF);
+const BOSS_PERIOD: Seconds = 60.0;
+const BOSS_BAR_WIDTH: i32 = 400;
+const BOSS_BAR_HEIGHT: i32 = 16;
+const BOSS_BAR_X: i32 = (DISPLAY_WIDTH as i32 - BOSS_BAR_WIDTH) / 2;
+const BOSS_BAR_Y: i32 = EFFECTS_LABEL_Y + FONT_CHAR_HEIGHT as i32 * WEAPON_LABEL_SCALE + SCORE_LABEL_PADDING;
+const BOSS_BAR_BACKGROUND: Pixel = Pixel::rgba(0x5F, 0x5F, 0x5F, 0xFF);
+const BOSS_BAR_COLOR: Pixel = Pixel::rgba(0xF0, 0x8C, 0x7C, 0xFF);
 const SCORE_LABEL_COLOR: Pixel = Pixel::rgba(0xDC, 0xDC, 0xCC, 0xFF);
 const SCORE_LABEL_PADDING: i32 = 17;
 const SCORE_LABEL_X: i32 = SCORE_LABEL_PADDING;
 const SCORE_LABEL_Y: i32 = SCORE_LABEL_PADDING;
 const SHADOW_COLOR: Pixel = Pixel::rgba(0x2B, 0x2B, 0x2B, 0xFF);
 const SHADOW_OFFSET: i32 = 4;
-const BULLETS_CAPACITY: usize = 5;
-const ENEMIES_CAPACITY: usize = 10;
+const BULLETS_CAPACITY: usize = 32;
+const ENEMY_BULLETS_CAPACITY: usize = 64;
+const ENEMIES_CAPACITY: usize = 16;
+// Bullet::hits has a bit per enemy slot
+const _: () = assert!(ENEMIES_CAPACITY <= 64);
 const ENEMY_SPAWN_PERIOD: Seconds = 1.0;
+const PICKUPS_CAPACITY: usize = 8;
+const PICKUP_SIZE: i32 = 30;
+const PICKUP_SPEED: i32 = ENEMY_SPEED * 2 / 3;
+// Chance in percents that a killed enemy drops a pickup
+const PICKUP_DROP_CHANCE: i32 = 15;
+const PICKUP_LETTER_COLOR: Pixel = Pixel::rgba(0x2B, 0x2B, 0x2B, 0xFF);
+const SHIELD_PERIOD: Seconds = 8.0;
+const SHIELD_SIZE: i32 = PLAYER_SIZE + 20;
+const SHIELD_COLOR: Pixel = Pixel::rgba(0x8C, 0xD0, 0xD3, 0xFF);
+const SCORE_MULTIPLIER: usize = 2;
+const SCORE_MULTIPLIER_PERIOD: Seconds = 10.0;
+// Kills within the window of each other build up a combo that
+// multiplies the reward
+const COMBO_WINDOW: Seconds = 1.5;
+const COMBO_MAX_MULTIPLIER: usize = 8;
+const COMBO_LABEL_COLOR: Pixel = PLAYER_COLOR;
+const SLOW_MOTION_FACTOR: f32 = 0.4;
+const SLOW_MOTION_PERIOD: Seconds = 5.0;
+const POPUPS_CAPACITY: usize = 16;
+const POPUP_SPEED: f32 = 60.0;
+// Random sideways speed, so the popups spawned at the same place
+// do not cover each other
+const POPUP_DRIFT: f32 = 30.0;
+const POPUP_SCALE: i32 = 2;
+const SCORE_POPUP_LIFETIME: Seconds = 0.8;
+const COMBO_POPUP_LIFETIME: Seconds = 1.2;
+const COMBO_POPUP_OFFSET: i32 = FONT_CHAR_HEIGHT as i32 * POPUP_SCALE * 2;
+const PICKUP_POPUP_LIFETIME: Seconds = 1.2;
+const PARTICLES_CAPACITY: usize = 512;
+// Fraction of the velocity the particles lose every second
+const PARTICLE_DRAG: f32 = 3.0;
+const EXPLOSION_EMITTER: Emitter = Emitter {
+    count: 24,
+    speed: ENEMY_SPEED as f32 * 2.0,
+    angle: 0.0,
+    spread: 2.0 * PI,
+    lifetime: 0.6,
+    size: 10,
+    color: ENEMY_COLOR,
+};
+const MUZZLE_FLASH_EMITTER: Emitter = Emitter {
+    count: 4,
+    speed: ENEMY_SPEED as f32,
+    angle: -PI / 2.0,
+    spread: PI / 2.0,
+    lifetime: 0.1,
+    size: 6,
+    color: BULLET_COLOR,
+};
+const TRAIL_EMITTER: Emitter = Emitter {
+    count: 1,
+    speed: ENEMY_SPEED as f32 / 4.0,
+    angle: PI / 2.0,
+    spread: PI / 4.0,
+    lifetime: 0.25,
+    size: 6,
+    color: BULLET_COLOR,
+};
+const PLAYER_HIT_EMITTER: Emitter = Emitter {
+    count: 32,
+    speed: ENEMY_SPEED as f32 * 1.5,
+    angle: 0.0,
+    spread: 2.0 * PI,
+    lifetime: 0.8,
+    size: 12,
+    color: PLAYER_COLOR,
+};
+// The music gets busier and faster when things get dangerous
+const MUSIC_MENU_INTENSITY: u32 = 0;
+const MUSIC_INTENSITY: u32 = 1;
+const MUSIC_BOSS_INTENSITY: u32 = 2;
+const MUSIC_BOSS_TEMPO: u32 = 115;
+const MUSIC_LOW_HEALTH_TEMPO: u32 = 125;
+const TITLE_TEXT: &[u8] = b"Bloat-Free Game";
+const TITLE_SCALE: i32 = 6;
+const TITLE_Y: i32 = 180;
+const PROMPT_TEXT: &[u8] = b"Click or press Enter";
+const PROMPT_SCALE: i32 = 3;
+const PROMPT_Y: i32 = 340;
+const PROMPT_BLINK_PERIOD: Seconds = 0.5;
+const HEADING_SCALE: i32 = 6;
+const HEADING_Y: i32 = 120;
+const MENU_SCALE: i32 = 4;
+const MENU_Y: i32 = 260;
+const MENU_ITEM_HEIGHT: i32 = FONT_CHAR_HEIGHT as i32 * MENU_SCALE + 24;
+const MENU_SELECTED_COLOR: Pixel = PLAYER_COLOR;
+const UI_HOVER_COLOR: Pixel = Pixel::rgba(0xFF, 0xFF, 0xFF, 0x18);
+const UI_HOVER_PADDING: i32 = 6;
+const UI_SLIDER_WIDTH: i32 = 200;
+const UI_SLIDER_HEIGHT: i32 = 16;
+const UI_SLIDER_GAP: i32 = 24;
+const SETTINGS_MENU_Y: i32 = 200;
+const VOLUME_STEP: i32 = 10;
+const CONFIG_MAGIC: [u8; 2] = [b'C', b'F'];
+const CONFIG_VERSION: u8 = 1;
+const CONFIG_SIZE: usize = 8;
+// Bytes exchanged with the host through get_blob()
+const BLOB_CAPACITY: usize = 256;
+const FPS_LABEL_SCALE: i32 = 2;
+// Under the life icons
+const TIME_LABEL_Y: i32 = LIFE_ICON_PADDING * 2 + LIFE_ICON_SIZE + SHADOW_OFFSET;
+const FPS_LABEL_Y: i32 = TIME_LABEL_Y + FONT_CHAR_HEIGHT as i32 * FPS_LABEL_SCALE + SCORE_LABEL_PADDING / 2;
+// How much of the new frame time gets into the displayed FPS
+const FPS_SMOOTHING: f32 = 0.05;
+const HIGH_SCORES_CAPACITY: usize = 10;
+const HIGH_SCORE_NAME_LENGTH: usize = 3;
+const HIGH_SCORES_MAGIC: [u8; 2] = [b'H', b'S'];
+const HIGH_SCORES_VERSION: u8 = 1;
+const HIGH_SCORES_HEADER_SIZE: usize = 4;
+const HIGH_SCORE_RECORD_SIZE: usize = HIGH_SCORE_NAME_LENGTH + 4;
+const _: () = assert!(HIGH_SCORES_HEADER_SIZE + HIGH_SCORES_CAPACITY * HIGH_SCORE_RECORD_SIZE <= BLOB_CAPACITY);
+const HIGH_SCORES_Y: i32 = 190;
+const HIGH_SCORES_SCALE: i32 = 3;
+const HIGH_SCORES_ROW_HEIGHT: i32 = 30;
+// Characters in a row like `10. ABC  123,456`
+const HIGH_SCORES_ROW_LENGTH: usize = 16;
+const HIGH_SCORES_MENU_Y: i32 = HIGH_SCORES_Y + HIGH_SCORES_ROW_HEIGHT * HIGH_SCORES_CAPACITY as i32 + 16;
+const NAME_ENTRY_SCORE_SCALE: i32 = 4;
+const NAME_ENTRY_SCORE_Y: i32 = 210;
+const NAME_ENTRY_SCALE: i32 = 8;
+const NAME_ENTRY_SPACING: i32 = 100;
+const NAME_ENTRY_Y: i32 = 290;
+const NAME_ENTRY_MENU_Y: i32 = 440;
+// Dims the game under the menus
+const OVERLAY_COLOR: Pixel = Pixel::rgba(0x2B, 0x2B, 0x2B, 0xA0);
+const EFFECTS_LABEL_Y: i32 = WEAPON_LABEL_Y + FONT_CHAR_HEIGHT as i32 * WEAPON_LABEL_SCALE + SCORE_LABEL_PADDING / 2;
+// The effects wrap onto the next lines past this width
+const EFFECTS_LABEL_WIDTH: i32 = DISPLAY_WIDTH as i32 / 2 - SCORE_LABEL_X;
 const FONT_IMAGE_WIDTH: usize = 128;
 const FONT_IMAGE_HEIGHT: usize = 64;
 const FONT_IMAGE_COLS: usize = 18;
 const FONT_IMAGE_ROWS: usize = 7;
 const FONT_CHAR_WIDTH: usize = FONT_IMAGE_WIDTH / FONT_IMAGE_COLS;
 const FONT_CHAR_HEIGHT: usize = FONT_IMAGE_HEIGHT / FONT_IMAGE_ROWS;
+// Gap between the lines of the text in the font pixels
+const TEXT_LINE_SPACING: i32 = 2;
+// Width to measure the text without wrapping it
+const TEXT_NO_WRAP: i32 = i32::MAX;
 const BITS_IN_BYTE: usize = 8;
 const COPYRIGHT_TEXT: &[u8] = b"Made by Tsoding";
 const COPYRIGHT_SCALE: i32 = 2;
-const COPYRIGHT_PADDING: usize = 10;
+const COPYRIGHT_PADDING: i32 = 10;
 // Generated from `./charmap-oldschool_white.png`
 const COMPRESSED_FONT: [u8; 622] = [
     0x00, 0x11, 0x20, 0xa1, 0x41, 0x0c, 0x0e, 0x08, 0x08, 0x40, 0x00, 0x05, 0x38, 0x20, 0x00, 0x01,
@@ -82,21 +418,64 @@ const COMPRESSED_FONT: [u8; 622] = [
     0x80, 0x00, 0x0c, 0x20, 0x20, 0x40, 0x80, 0x00, 0x0c, 0x7c, 0x10, 0x41, 0x00, 0xbd,
 ];
 
-const RNG_A: i32 = 1103515245;
-const RNG_C: i32 = 12345;
+const WAVE_SPACING: i32 = 3;
+// Scripted waves compiled from `./level.txt` by `./levelc.rs`
+const LEVEL: &[u8] = include_bytes!("level.bin");
+
+const RNG_DEFAULT_SEED: u64 = 123456789;
+const RNG_MULTIPLIER: u64 = 6364136223846793005;
+// Generators seeded with the same seed but different streams produce
+// independent sequences
+const RNG_GAMEPLAY_STREAM: u64 = 0;
+const RNG_COSMETIC_STREAM: u64 = 1;
+const RNG_POPUP_STREAM: u64 = 2;
 
+// PCG32 (XSH RR variant) https://www.pcg-random.org/
 struct Rng {
-    seed: i32,
+    state: u64,
+    increment: u64,
 }
 
 impl Rng {
-    const fn from_seed(seed: i32) -> Self {
-        Self {seed}
+    const fn new(seed: u64, stream: u64) -> Self {
+        let increment = (stream << 1) | 1;
+        let state = increment.wrapping_add(seed);
+        Self {
+            state: state.wrapping_mul(RNG_MULTIPLIER).wrapping_add(increment),
+            increment,
+        }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(RNG_MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
     }
 
-    fn rand(&mut self) -> i32 {
-        self.seed = RNG_A * self.seed + RNG_C;
-        self.seed
+    // Uniformly distributed number in [0, n). Returns 0 for n <= 0.
+    fn range(&mut self, n: i32) -> i32 {
+        if n <= 0 {
+            return 0;
+        }
+
+        // Rejecting the values below 2^32 % n leaves a multiple of n
+        // possible values, so every remainder is equally likely
+        let n = n as u32;
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let x = self.next_u32();
+            if x >= threshold {
+                return (x % n) as i32;
+            }
+        }
+    }
+
+    // Uniformly distributed number in [0, 1)
+    fn unit(&mut self) -> f32 {
+        // f32 has 24 bits of precision
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
     }
 }
 
@@ -114,6 +493,29 @@ impl Pixel {
                 | ((r as u32) << (0 * 8)),
         )
     }
+
+    const fn alpha(self) -> u8 {
+        (self.0 >> (3 * 8)) as u8
+    }
+
+    const fn with_alpha(self, a: u8) -> Self {
+        Self((self.0 & 0x00FFFFFF) | ((a as u32) << (3 * 8)))
+    }
+
+    // Draws self over the opaque background using self's alpha
+    const fn blend(self, background: Self) -> Self {
+        let a = self.alpha() as u32;
+        let mut result = 0xFF << (3 * 8);
+        let mut channel = 0;
+        while channel < 3 {
+            let shift = channel * 8;
+            let src = (self.0 >> shift) & 0xFF;
+            let dst = (background.0 >> shift) & 0xFF;
+            result |= ((src * a + dst * (0xFF - a)) / 0xFF) << shift;
+            channel += 1;
+        }
+        Self(result)
+    }
 }
 
 #[repr(C)]
@@ -163,16 +565,72 @@ impl Display {
         }
     }
 
+    // Like fill_rect, but respects the alpha of the pixel
+    fn blend_rect(&mut self, x0: i32, y0: i32, w: i32, h: i32, pixel: Pixel) {
+        let x1 = clamp(x0, 0, (DISPLAY_WIDTH - 1) as i32) as usize;
+        let x2 = clamp(x0 + w - 1, 0, (DISPLAY_WIDTH - 1) as i32) as usize;
+        let y1 = clamp(y0, 0, (DISPLAY_HEIGHT - 1) as i32) as usize;
+        let y2 = clamp(y0 + h - 1, 0, (DISPLAY_HEIGHT - 1) as i32) as usize;
+
+        for y in y1..=y2 {
+            for x in x1..=x2 {
+                if let Some(pixel_ref) = self.pixels.get_mut(y * DISPLAY_WIDTH + x) {
+                    *pixel_ref = pixel.blend(*pixel_ref)
+                }
+            }
+        }
+    }
+
+    // Translucent pixels are blended with the background. Whatever is
+    // outside of the display is dropped instead of wrapping onto the
+    // neighbouring row.
     fn put(&mut self, x: i32, y: i32, pixel: Pixel) {
+        if x < 0 || x >= DISPLAY_WIDTH as i32 || y < 0 || y >= DISPLAY_HEIGHT as i32 {
+            return;
+        }
         let index = y as usize * DISPLAY_WIDTH + x as usize;
         if let Some(pixel_ref) = self.pixels.get_mut(index) {
-            *pixel_ref = pixel;
+            *pixel_ref = if pixel.alpha() == 0xFF {
+                pixel
+            } else {
+                pixel.blend(*pixel_ref)
+            };
         }
     }
 }
 
 type Seconds = f32;
 
+#[derive(Copy, Clone)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+impl Point {
+    const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Rect {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+impl Rect {
+    const fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    const fn contains(&self, x: i32, y: i32) -> bool {
+        self.x <= x && x < self.x + self.w && self.y <= y && y < self.y + self.h
+    }
+}
+
 #[derive(Copy, Clone)]
 #[repr(C)]
 struct Entity {
@@ -224,320 +682,3072 @@ impl Entity {
     }
 }
 
-struct Font {
-    pixels: [u8; FONT_IMAGE_WIDTH * FONT_IMAGE_HEIGHT],
+#[derive(Clone, Copy, PartialEq)]
+enum WeaponKind {
+    Blaster,
+    Spread,
+    Rapid,
+    Laser,
+    Homing,
 }
 
-impl Font {
-    fn decompress_from_bytes(&mut self, bytes: &[u8]) {
-        let n = bytes.len();
-        let mut i = 0;
-        let mut pixels_size: usize = 0;
-        while i < n {
-            if let Some(byte) = bytes.get(i).cloned() {
-                if byte == 0x00 {
-                    i += 1;
-                    if let Some(next_byte) = bytes.get(i).cloned() {
-                        pixels_size += next_byte as usize * 8;
-                    } else {
-                        break;
-                    }
-                    i += 1;
-                } else {
-                    for bit_index in 0..BITS_IN_BYTE {
-                        if let Some(pixel_ref) = self.pixels.get_mut(pixels_size) {
-                            *pixel_ref = ((byte >> (BITS_IN_BYTE - bit_index - 1)) & 1) * 0xFF;
-                        } else {
-                            break;
-                        }
-                        pixels_size += 1;
-                    }
-                    i += 1;
-                }
-            } else {
-                break;
-            }
+const WEAPONS_COUNT: usize = 5;
+
+struct Weapon {
+    name: &'static [u8],
+    cooldown: Seconds,
+    speed: i32,
+    damage: i32,
+    size: i32,
+    color: Pixel,
+    projectiles: usize,
+    projectiles_per_level: usize,
+    // Horizontal speed difference between neighbouring projectiles
+    // of the same shot
+    spread: f32,
+    // Piercing projectiles keep flying after hitting an enemy
+    pierce: bool,
+    // Homing projectiles steer towards the closest enemy
+    homing: bool,
+    // Projectiles leave a trail of particles behind
+    trail: bool,
+}
+
+impl WeaponKind {
+    const fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Self::Blaster),
+            1 => Some(Self::Spread),
+            2 => Some(Self::Rapid),
+            3 => Some(Self::Laser),
+            4 => Some(Self::Homing),
+            _ => None,
         }
     }
 
-    fn get(&self, x: i32, y: i32) -> Option<&u8> {
-        self.pixels.get(y as usize * FONT_IMAGE_WIDTH + x as usize)
+    const fn weapon(self) -> Weapon {
+        match self {
+            Self::Blaster => Weapon {
+                name: b"Blaster",
+                cooldown: 0.25,
+                speed: BULLET_SPEED,
+                damage: 3,
+                size: 25,
+                color: BULLET_COLOR,
+                projectiles: 1,
+                projectiles_per_level: 0,
+                spread: 0.0,
+                pierce: false,
+                homing: false,
+                trail: false,
+            },
+            Self::Spread => Weapon {
+                name: b"Spread",
+                cooldown: 0.45,
+                speed: BULLET_SPEED * 3 / 4,
+                damage: 2,
+                size: 18,
+                color: Pixel::rgba(0xF0, 0xDF, 0xAF, 0xFF),
+                projectiles: 3,
+                projectiles_per_level: 2,
+                spread: BULLET_SPEED as f32 / 6.0,
+                pierce: false,
+                homing: false,
+                trail: false,
+            },
+            Self::Rapid => Weapon {
+                name: b"Rapid",
+                cooldown: 0.08,
+                speed: BULLET_SPEED * 3 / 2,
+                damage: 1,
+                size: 12,
+                color: Pixel::rgba(0xDC, 0xA3, 0xA3, 0xFF),
+                projectiles: 1,
+                projectiles_per_level: 0,
+                spread: 0.0,
+                pierce: false,
+                homing: false,
+                trail: false,
+            },
+            Self::Laser => Weapon {
+                name: b"Laser",
+                cooldown: 0.5,
+                speed: BULLET_SPEED * 2,
+                damage: 2,
+                size: 14,
+                color: Pixel::rgba(0x93, 0xE0, 0xE3, 0xFF),
+                projectiles: 1,
+                projectiles_per_level: 0,
+                spread: 0.0,
+                pierce: true,
+                homing: false,
+                trail: true,
+            },
+            Self::Homing => Weapon {
+                name: b"Homing",
+                cooldown: 0.7,
+                speed: BULLET_SPEED / 2,
+                damage: 3,
+                size: 20,
+                color: Pixel::rgba(0xBF, 0xEB, 0xBF, 0xFF),
+                projectiles: 1,
+                projectiles_per_level: 1,
+                spread: BULLET_SPEED as f32 / 3.0,
+                pierce: false,
+                homing: true,
+                trail: true,
+            },
+        }
     }
+}
 
-    fn render_ascii(&self,
-                    display: &mut Display,
-                    code: u8,
-                    start_x: i32, start_y: i32,
-                    scale: i32,
-                    color: Pixel) {
-        if 32 <= code && code <= 126 {
-            let char_x = (code - 32) as usize % FONT_IMAGE_COLS;
-            let char_y = (code - 32) as usize / FONT_IMAGE_COLS;
-
-            for y in 0..FONT_CHAR_HEIGHT as i32 {
-                for x in 0..FONT_CHAR_WIDTH as i32 {
-                    for scale_x in 0..scale {
-                        for scale_y in 0..scale {
-                            let font_x = char_x as i32 * FONT_CHAR_WIDTH as i32 + x;
-                            let font_y = char_y as i32 * FONT_CHAR_HEIGHT as i32 + y;
-                            let display_x = start_x + x * scale + scale_x;
-                            let display_y = start_y + y * scale + scale_y;
-
-                            if let Some(alpha) = self.get(font_x, font_y) {
-                                if *alpha == 0xFF {
-                                    display.put(display_x, display_y, color);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        } else {
-            self.render_ascii(display, '?' as u8, start_x, start_y, scale, color)
-        }
+impl Weapon {
+    fn projectiles(&self, level: usize) -> usize {
+        self.projectiles + self.projectiles_per_level * level
     }
 
-    fn render_bytes(&self,
-                    display: &mut Display,
-                    bytes: &[u8],
-                    x: i32, y: i32,
-                    scale: i32,
-                    color: Pixel) {
-        for (i, byte) in bytes.iter().enumerate() {
-            self.render_ascii(
-                display,
-                *byte,
-                x + i as i32 * FONT_CHAR_WIDTH as i32 * scale, y,
-                scale,
-                color);
-        }
+    fn cooldown(&self, level: usize) -> Seconds {
+        // Every level makes the weapon fire 20% faster
+        self.cooldown / (1.0 + 0.2 * level as f32)
     }
 }
 
-const LABEL_CAPACITY: usize = 64;
-struct Label {
-    chars: [u8; LABEL_CAPACITY],
-    count: usize,
+#[derive(Copy, Clone)]
+struct Bullet {
+    entity: Entity,
+    kind: WeaponKind,
+    vx: f32,
+    vy: f32,
+    // Bit per enemy slot this bullet has already damaged, so
+    // piercing bullets hit every enemy only once
+    hits: u64,
 }
 
-impl Label {
-    const fn empty() -> Self {
+impl Bullet {
+    const fn dead() -> Self {
         Self {
-            chars: [0; LABEL_CAPACITY],
-            count: 0,
+            entity: Entity::dead(),
+            kind: WeaponKind::Blaster,
+            vx: 0.0,
+            vy: 0.0,
+            hits: 0,
         }
     }
 
-    fn render(&self,
-              display: &mut Display,
-              font: &Font,
-              x: i32, y: i32,
-              scale: i32,
-              color: Pixel) {
-        if let Some(bytes) = self.chars.get(0..self.count) {
-            font.render_bytes(display, bytes, x - SHADOW_OFFSET, y - SHADOW_OFFSET, scale, SHADOW_COLOR);
-            font.render_bytes(display, bytes, x, y, scale, color);
-        }
+    fn render(&self, display: &mut Display) {
+        let weapon = self.kind.weapon();
+        self.entity.render(display, weapon.size, weapon.color);
     }
+}
 
-    fn clear(&mut self) {
-        self.count = 0;
-    }
+#[derive(Clone, Copy, PartialEq)]
+enum EnemyKind {
+    Basic,
+    ZigZag,
+    Diver,
+    Tank,
+    Splitter,
+    // Spawned in pairs when a Splitter dies
+    Splitling,
+    // Spawned every BOSS_PERIOD seconds, see BOSS_PHASES
+    Boss,
+}
 
-    fn push_byte(&mut self, b: u8) {
-        if let Some(char_ref) = self.chars.get_mut(self.count) {
-            *char_ref = b;
-            self.count += 1;
-        }
-    }
+// The order must match level::ENEMY_KIND_NAMES
+const ENEMY_KINDS: [EnemyKind; 7] = [
+    EnemyKind::Basic,
+    EnemyKind::ZigZag,
+    EnemyKind::Diver,
+    EnemyKind::Tank,
+    EnemyKind::Splitter,
+    EnemyKind::Splitling,
+    EnemyKind::Boss,
+];
+const _: () = assert!(ENEMY_KINDS.len() == level::ENEMY_KIND_NAMES.len());
 
-    fn push_bytes(&mut self, bs: &[u8]) {
-        for b in bs {
-            self.push_byte(*b);
-        }
-    }
+// Every movement is a displacement from the spawn position of the
+// enemy as a function of its age. Enemies of the same formation share
+// the movement and the age so the formation moves as a group.
+#[derive(Clone, Copy)]
+enum Movement {
+    Straight,
+    // Sine wave around the spawn position
+    ZigZag {
+        amplitude: f32,
+        frequency: f32,
+    },
+    // Steers horizontally towards the player
+    Chase {
+        speed: i32,
+    },
+    // Descends by the given depth and swings around there
+    Hover {
+        depth: i32,
+        amplitude: f32,
+        frequency: f32,
+    },
+    // Cubic Bezier curve through the control points relative to the
+    // spawn position. Falls straight down after the curve is over.
+    Bezier {
+        points: &'static [Point; 4],
+        duration: Seconds,
+    },
+    // Catmull-Rom spline passing through all of the points relative
+    // to the spawn position. Falls straight down after the spline is
+    // over.
+    Spline {
+        points: &'static [Point],
+        duration: Seconds,
+    },
+    // Circles around a center slowly sinking down
+    Orbit {
+        radius: f32,
+        angular_speed: f32,
+    },
+}
 
-    fn push_usize(&mut self, mut x: usize) {
-        let saved_count = self.count;
+const SWOOP_LEFT: [Point; 4] = [
+    Point::new(0.0, 0.0),
+    Point::new(0.0, 500.0),
+    Point::new(-300.0, 500.0),
+    Point::new(-300.0, 100.0),
+];
+const SWOOP_RIGHT: [Point; 4] = [
+    Point::new(0.0, 0.0),
+    Point::new(0.0, 500.0),
+    Point::new(300.0, 500.0),
+    Point::new(300.0, 100.0),
+];
+const SNAKE: [Point; 6] = [
+    Point::new(0.0, 0.0),
+    Point::new(150.0, 100.0),
+    Point::new(-150.0, 200.0),
+    Point::new(150.0, 300.0),
+    Point::new(-150.0, 400.0),
+    Point::new(0.0, 500.0),
+];
 
-        if x == 0 {
-            self.push_byte(b'0');
-        } else {
-            while x > 0 && self.count < LABEL_CAPACITY {
-                self.push_byte((x % 10) as u8 + b'0');
-                x /= 10;
-            }
+fn bezier(points: &[Point; 4], t: f32) -> Point {
+    let [p0, p1, p2, p3] = *points;
+    let u = 1.0 - t;
+    let a = u * u * u;
+    let b = 3.0 * u * u * t;
+    let c = 3.0 * u * t * t;
+    let d = t * t * t;
+    Point::new(
+        a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+        a * p0.y + b * p1.y + c * p2.y + d * p3.y,
+    )
+}
 
-            if x > 0 {
-                // x does not fit into the Label rolling back and quitting
-                self.count = saved_count;
-                return;
-            }
-        }
+fn catmull_rom(points: &[Point], t: f32) -> Point {
+    let last = points.len().saturating_sub(1);
+    let position = t * last as f32;
+    let i = (position as usize).min(last.saturating_sub(1));
+    let t = position - i as f32;
+
+    // The end points are repeated to make the spline pass through them
+    let point = |i: usize| points.get(i.min(last)).cloned().unwrap_or(Point::new(0.0, 0.0));
+    let (p0, p1, p2, p3) = (point(i.saturating_sub(1)), point(i), point(i + 1), point(i + 2));
+
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let f = |a: f32, b: f32, c: f32, d: f32| {
+        0.5 * (2.0 * b + (c - a) * t + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2 + (3.0 * b - a - 3.0 * c + d) * t3)
+    };
+    Point::new(f(p0.x, p1.x, p2.x, p3.x), f(p0.y, p1.y, p2.y, p3.y))
+}
 
-        if let Some(chars) = self.chars.get_mut(saved_count .. self.count) {
-            chars.reverse();
+impl Movement {
+    // `fall` is how far the enemy would have fallen moving straight
+    // down with its normal speed
+    fn displacement(self, age: Seconds, speed: i32) -> Point {
+        let fall = speed as f32 * age;
+        match self {
+            Self::Straight | Self::Chase { .. } => Point::new(0.0, fall),
+            Self::ZigZag { amplitude, frequency } => {
+                Point::new(amplitude * sin(2.0 * PI * frequency * age), fall)
+            }
+            Self::Hover { depth, amplitude, frequency } => Point::new(
+                amplitude * sin(2.0 * PI * frequency * age),
+                fall.min(depth as f32),
+            ),
+            Self::Bezier { points, duration } => {
+                let end = bezier(points, 1.0);
+                if age < duration {
+                    bezier(points, age / duration)
+                } else {
+                    Point::new(end.x, end.y + speed as f32 * (age - duration))
+                }
+            }
+            Self::Spline { points, duration } => {
+                let end = catmull_rom(points, 1.0);
+                if age < duration {
+                    catmull_rom(points, age / duration)
+                } else {
+                    Point::new(end.x, end.y + speed as f32 * (age - duration))
+                }
+            }
+            Self::Orbit { radius, angular_speed } => {
+                let angle = angular_speed * age;
+                // Start at the top of the circle
+                Point::new(radius * sin(angle), radius - radius * cos(angle) + fall / 3.0)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Fire {
+    Never,
+    // Single bullet flying towards the player
+    Aimed {
+        period: Seconds,
+        speed: i32,
+    },
+    // Ring of bullets evenly spread in all directions
+    Radial {
+        period: Seconds,
+        count: usize,
+        speed: i32,
+    },
+    // Stream of bullets rotating with the given angular speed
+    Spiral {
+        period: Seconds,
+        speed: i32,
+        spin: f32,
+    },
+}
+
+impl Fire {
+    const fn period(self) -> Seconds {
+        match self {
+            Self::Never => 0.0,
+            Self::Aimed { period, .. }
+            | Self::Radial { period, .. }
+            | Self::Spiral { period, .. } => period,
+        }
+    }
+}
+
+struct BossPhase {
+    // The phase is active while the boss has more than this
+    // percentage of health left
+    health_percent: i32,
+    fire: Fire,
+}
+
+const BOSS_PHASES: [BossPhase; 3] = [
+    BossPhase {
+        health_percent: 66,
+        fire: Fire::Aimed {
+            period: 0.6,
+            speed: ENEMY_BULLET_SPEED * 3 / 2,
+        },
+    },
+    BossPhase {
+        health_percent: 33,
+        fire: Fire::Radial {
+            period: 1.2,
+            count: 16,
+            speed: ENEMY_BULLET_SPEED,
+        },
+    },
+    BossPhase {
+        health_percent: 0,
+        fire: Fire::Spiral {
+            period: 0.07,
+            speed: ENEMY_BULLET_SPEED,
+            spin: 5.0,
+        },
+    },
+];
+
+fn boss_phase(health: i32) -> Fire {
+    let percent = health * 100 / BOSS_HEALTH;
+    for phase in BOSS_PHASES.iter() {
+        if percent > phase.health_percent {
+            return phase.fire;
+        }
+    }
+    Fire::Never
+}
+
+struct EnemyClass {
+    size: i32,
+    color: Pixel,
+    health: i32,
+    reward: usize,
+    speed: i32,
+    movement: Movement,
+    fire: Fire,
+    // Relative chance to be picked by the random spawner
+    spawn_weight: i32,
+}
+
+impl EnemyKind {
+    const fn class(self) -> EnemyClass {
+        match self {
+            Self::Basic => EnemyClass {
+                size: ENEMY_SIZE,
+                color: ENEMY_COLOR,
+                health: ENEMY_HEALTH,
+                reward: PLAYER_KILL_REWARD,
+                speed: ENEMY_SPEED,
+                movement: Movement::Straight,
+                fire: Fire::Never,
+                spawn_weight: 6,
+            },
+            Self::ZigZag => EnemyClass {
+                size: 70,
+                color: Pixel::rgba(0x9F, 0xC5, 0x9F, 0xFF),
+                health: 2,
+                reward: PLAYER_KILL_REWARD * 3 / 2,
+                speed: ENEMY_SPEED * 3 / 4,
+                movement: Movement::ZigZag {
+                    amplitude: 120.0,
+                    frequency: 0.5,
+                },
+                fire: Fire::Aimed {
+                    period: 2.0,
+                    speed: ENEMY_BULLET_SPEED,
+                },
+                spawn_weight: 3,
+            },
+            Self::Diver => EnemyClass {
+                size: 40,
+                color: Pixel::rgba(0xDC, 0xA3, 0xA3, 0xFF),
+                health: 1,
+                reward: PLAYER_KILL_REWARD * 2,
+                speed: ENEMY_SPEED * 2,
+                movement: Movement::Chase {
+                    speed: ENEMY_SPEED / 2,
+                },
+                fire: Fire::Never,
+                spawn_weight: 2,
+            },
+            Self::Tank => EnemyClass {
+                size: 150,
+                color: Pixel::rgba(0x6C, 0x8C, 0xA0, 0xFF),
+                health: ENEMY_HEALTH * 5,
+                reward: PLAYER_KILL_REWARD * 5,
+                speed: ENEMY_SPEED / 3,
+                movement: Movement::Straight,
+                fire: Fire::Radial {
+                    period: 3.0,
+                    count: 8,
+                    speed: ENEMY_BULLET_SPEED * 2 / 3,
+                },
+                spawn_weight: 1,
+            },
+            Self::Splitter => EnemyClass {
+                size: ENEMY_SIZE,
+                color: Pixel::rgba(0xDC, 0x8C, 0xC3, 0xFF),
+                health: ENEMY_HEALTH,
+                reward: PLAYER_KILL_REWARD,
+                speed: ENEMY_SPEED / 2,
+                movement: Movement::Straight,
+                fire: Fire::Aimed {
+                    period: 2.5,
+                    speed: ENEMY_BULLET_SPEED,
+                },
+                spawn_weight: 2,
+            },
+            Self::Splitling => EnemyClass {
+                size: ENEMY_SIZE / 2,
+                color: Pixel::rgba(0xEC, 0xAC, 0xD3, 0xFF),
+                health: 1,
+                reward: PLAYER_KILL_REWARD / 2,
+                speed: ENEMY_SPEED,
+                movement: Movement::ZigZag {
+                    amplitude: 40.0,
+                    frequency: 1.5,
+                },
+                fire: Fire::Never,
+                spawn_weight: 0,
+            },
+            Self::Boss => EnemyClass {
+                size: BOSS_SIZE,
+                color: BOSS_COLOR,
+                health: BOSS_HEALTH,
+                reward: PLAYER_KILL_REWARD * 50,
+                speed: ENEMY_SPEED / 3,
+                movement: Movement::Hover {
+                    depth: BOSS_SIZE * 5 / 4,
+                    amplitude: (DISPLAY_WIDTH as i32 - BOSS_SIZE) as f32 / 2.0,
+                    frequency: 0.15,
+                },
+                // Depends on the current phase, see BOSS_PHASES
+                fire: Fire::Never,
+                spawn_weight: 0,
+            },
+        }
+    }
+
+    fn random(rng: &mut Rng) -> Self {
+        let mut total = 0;
+        for kind in ENEMY_KINDS.iter() {
+            total += kind.class().spawn_weight;
+        }
+
+        let mut roll = rng.range(total);
+        for kind in ENEMY_KINDS.iter() {
+            let weight = kind.class().spawn_weight;
+            if roll < weight {
+                return *kind;
+            }
+            roll -= weight;
+        }
+        EnemyKind::Basic
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Enemy {
+    entity: Entity,
+    kind: EnemyKind,
+    health: i32,
+    // Seconds since the enemy was spawned
+    age: Seconds,
+    origin: Point,
+    // Position within the formation
+    offset: Point,
+    movement: Movement,
+    fire_cooldown: Seconds,
+}
+
+impl Enemy {
+    const fn dead() -> Self {
+        Self {
+            entity: Entity::dead(),
+            kind: EnemyKind::Basic,
+            health: 0,
+            age: 0.0,
+            origin: Point::new(0.0, 0.0),
+            offset: Point::new(0.0, 0.0),
+            movement: Movement::Straight,
+            fire_cooldown: 0.0,
+        }
+    }
+
+    fn size(&self) -> i32 {
+        self.kind.class().size
+    }
+
+    fn place(&mut self) {
+        let displacement = self.movement.displacement(self.age, self.kind.class().speed);
+        self.entity.x = (self.origin.x + self.offset.x + displacement.x) as i32;
+        self.entity.y = (self.origin.y + self.offset.y + displacement.y) as i32;
+    }
+
+    fn update(&mut self, dt: Seconds, player_x: i32) {
+        let class = self.kind.class();
+        self.age += dt;
+        if let Movement::Chase { speed } = self.movement {
+            let step = speed as f32 * dt;
+            let dx = (player_x - self.entity.x) as f32;
+            self.origin.x += dx.max(-step).min(step);
+        }
+        self.place();
+
+        // Despawn the enemy if it went outside of the screen
+        if self.entity.y - class.size / 2 > DISPLAY_HEIGHT as i32 {
+            self.entity.alive = false
+        }
+    }
+
+    fn fire(&self) -> Fire {
+        if self.kind == EnemyKind::Boss {
+            boss_phase(self.health)
+        } else {
+            self.kind.class().fire
+        }
+    }
+
+    // Returns true when the enemy is ready to fire according to its
+    // schedule. Enemies do not shoot until they are on the screen.
+    fn reload(&mut self, dt: Seconds) -> bool {
+        let fire = self.fire();
+        if let Fire::Never = fire {
+            return false;
+        }
+
+        self.fire_cooldown -= dt;
+        if self.fire_cooldown <= 0.0 && self.entity.y >= 0 {
+            self.fire_cooldown = fire.period();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn render(&self, display: &mut Display) {
+        let class = self.kind.class();
+        self.entity.render(display, class.size, class.color);
+    }
+}
+
+#[derive(Copy, Clone)]
+struct EnemyBullet {
+    entity: Entity,
+    vx: f32,
+    vy: f32,
+}
+
+impl EnemyBullet {
+    const fn dead() -> Self {
+        Self {
+            entity: Entity::dead(),
+            vx: 0.0,
+            vy: 0.0,
+        }
+    }
+
+    fn update(&mut self, dt: Seconds) {
+        self.entity.x += (self.vx * dt) as i32;
+        self.entity.y += (self.vy * dt) as i32;
+        if self.entity.x + ENEMY_BULLET_SIZE / 2 < 0
+            || self.entity.x - ENEMY_BULLET_SIZE / 2 > DISPLAY_WIDTH as i32
+            || self.entity.y + ENEMY_BULLET_SIZE / 2 < 0
+            || self.entity.y - ENEMY_BULLET_SIZE / 2 > DISPLAY_HEIGHT as i32
+        {
+            self.entity.alive = false
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Particle {
+    position: Point,
+    velocity: Point,
+    age: Seconds,
+    lifetime: Seconds,
+    size: i32,
+    color: Pixel,
+    alive: bool,
+}
+
+impl Particle {
+    const fn dead() -> Self {
+        Self {
+            position: Point::new(0.0, 0.0),
+            velocity: Point::new(0.0, 0.0),
+            age: 0.0,
+            lifetime: 0.0,
+            size: 0,
+            color: Pixel(0),
+            alive: false,
+        }
+    }
+
+    fn update(&mut self, dt: Seconds) {
+        self.age += dt;
+        if self.age >= self.lifetime {
+            self.alive = false;
+        }
+        self.position.x += self.velocity.x * dt;
+        self.position.y += self.velocity.y * dt;
+        let drag = (1.0 - PARTICLE_DRAG * dt).max(0.0);
+        self.velocity.x *= drag;
+        self.velocity.y *= drag;
+    }
+
+    // Fades out and shrinks to the half of the size by the end of
+    // the lifetime
+    fn render(&self, display: &mut Display) {
+        if self.alive {
+            let left = 1.0 - self.age / self.lifetime;
+            let size = max((self.size as f32 * (0.5 + left / 2.0)) as i32, 1);
+            let alpha = (self.color.alpha() as f32 * left) as u8;
+            display.blend_rect(self.position.x as i32 - size / 2,
+                               self.position.y as i32 - size / 2,
+                               size, size,
+                               self.color.with_alpha(alpha));
+        }
+    }
+}
+
+// Text floating up from a place in the world and fading out
+#[derive(Clone, Copy)]
+struct Popup {
+    label: Label,
+    position: Point,
+    velocity: Point,
+    age: Seconds,
+    lifetime: Seconds,
+    color: Pixel,
+    alive: bool,
+}
+
+impl Popup {
+    const fn dead() -> Self {
+        Self {
+            label: Label::new(),
+            position: Point::new(0.0, 0.0),
+            velocity: Point::new(0.0, 0.0),
+            age: 0.0,
+            lifetime: 0.0,
+            color: Pixel(0),
+            alive: false,
+        }
+    }
+
+    fn update(&mut self, dt: Seconds) {
+        self.age += dt;
+        if self.age >= self.lifetime {
+            self.alive = false;
+        }
+        self.position.x += self.velocity.x * dt;
+        self.position.y += self.velocity.y * dt;
+    }
+
+    fn render(&self, display: &mut Display, font: &Font) {
+        if self.alive {
+            let alpha = (self.color.alpha() as f32 * (1.0 - self.age / self.lifetime)) as u8;
+            let width = self.label.width(font, POPUP_SCALE);
+            self.label.render(display, font,
+                              self.position.x as i32 - width / 2,
+                              self.position.y as i32,
+                              POPUP_SCALE,
+                              self.color.with_alpha(alpha));
+        }
+    }
+}
+
+// Every scene can float the text over whatever is on the screen. The
+// gameplay gets the pool passed into Game::update like the audio.
+struct Popups {
+    popups: [Popup; POPUPS_CAPACITY],
+    rng: Rng,
+}
+
+impl Popups {
+    const fn new(seed: u64) -> Self {
+        Self {
+            popups: [Popup::dead(); POPUPS_CAPACITY],
+            rng: Rng::new(seed, RNG_POPUP_STREAM),
+        }
+    }
+
+    // Returns the label of the popup to fill in
+    fn spawn(&mut self, x: i32, y: i32, lifetime: Seconds, color: Pixel) -> Option<&mut Label> {
+        let drift = POPUP_DRIFT * (self.rng.unit() - 0.5) * 2.0;
+        for popup in self.popups.iter_mut() {
+            if !popup.alive {
+                popup.alive = true;
+                popup.label.clear();
+                popup.position = Point::new(x as f32, y as f32);
+                popup.velocity = Point::new(drift, -POPUP_SPEED);
+                popup.age = 0.0;
+                popup.lifetime = lifetime;
+                popup.color = color;
+                return Some(&mut popup.label);
+            }
+        }
+        None
+    }
+
+    fn update(&mut self, dt: Seconds) {
+        for popup in self.popups.iter_mut() {
+            if popup.alive {
+                popup.update(dt);
+            }
+        }
+    }
+
+    fn render(&self, display: &mut Display, font: &Font) {
+        for popup in self.popups.iter() {
+            popup.render(display, font)
+        }
+    }
+}
+
+// Describes a burst of particles flying out of a point in random
+// directions within `spread` radians around `angle`
+#[derive(Clone, Copy)]
+struct Emitter {
+    count: usize,
+    speed: f32,
+    angle: f32,
+    spread: f32,
+    lifetime: Seconds,
+    size: i32,
+    color: Pixel,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PickupKind {
+    ExtraLife,
+    Shield,
+    WeaponUpgrade,
+    ScoreMultiplier,
+    SlowMotion,
+}
+
+// Relative chances of the pickup kinds to drop
+const PICKUP_WEIGHTS: [(PickupKind, i32); 5] = [
+    (PickupKind::ExtraLife, 1),
+    (PickupKind::Shield, 3),
+    (PickupKind::WeaponUpgrade, 4),
+    (PickupKind::ScoreMultiplier, 3),
+    (PickupKind::SlowMotion, 2),
+];
+
+impl PickupKind {
+    fn random(rng: &mut Rng) -> Self {
+        let mut total = 0;
+        for (_, weight) in PICKUP_WEIGHTS.iter() {
+            total += weight;
+        }
+
+        let mut roll = rng.range(total);
+        for (kind, weight) in PICKUP_WEIGHTS.iter() {
+            if roll < *weight {
+                return *kind;
+            }
+            roll -= weight;
+        }
+        PickupKind::WeaponUpgrade
+    }
+
+    const fn color(self) -> Pixel {
+        match self {
+            Self::ExtraLife => PLAYER_COLOR,
+            Self::Shield => SHIELD_COLOR,
+            Self::WeaponUpgrade => Pixel::rgba(0xF0, 0xDF, 0xAF, 0xFF),
+            Self::ScoreMultiplier => Pixel::rgba(0xDC, 0xA3, 0xA3, 0xFF),
+            Self::SlowMotion => Pixel::rgba(0xBF, 0xEB, 0xBF, 0xFF),
+        }
+    }
+
+    const fn popup(self) -> &'static [u8] {
+        match self {
+            Self::ExtraLife => b"EXTRA LIFE",
+            Self::Shield => b"SHIELD UP",
+            Self::WeaponUpgrade => b"WEAPON UP",
+            Self::ScoreMultiplier => b"SCORE x2",
+            Self::SlowMotion => b"SLOW MOTION",
+        }
+    }
+
+    const fn letter(self) -> u8 {
+        match self {
+            Self::ExtraLife => b'L',
+            Self::Shield => b'S',
+            Self::WeaponUpgrade => b'W',
+            Self::ScoreMultiplier => b'X',
+            Self::SlowMotion => b'T',
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Pickup {
+    entity: Entity,
+    kind: PickupKind,
+}
+
+impl Pickup {
+    const fn dead() -> Self {
+        Self {
+            entity: Entity::dead(),
+            kind: PickupKind::WeaponUpgrade,
+        }
+    }
+
+    fn render(&self, display: &mut Display, font: &Font) {
+        if self.entity.alive {
+            self.entity.render(display, PICKUP_SIZE, self.kind.color());
+            let scale = 2;
+            font.render_ascii(
+                display,
+                self.kind.letter(),
+                self.entity.x - FONT_CHAR_WIDTH as i32 * scale / 2,
+                self.entity.y - FONT_CHAR_HEIGHT as i32 * scale / 2,
+                scale,
+                PICKUP_LETTER_COLOR);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Formation {
+    Single,
+    // Horizontal row centered around the wave position
+    Line,
+    // Enemies following each other from above the display
+    Column,
+    // Leader in front with the rest trailing behind to both sides
+    V,
+    // Rows of enemies as close to a square as possible
+    Grid,
+}
+
+impl Formation {
+    // Follows the order of level::FORMATION_NAMES
+    const fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Single),
+            1 => Some(Self::Line),
+            2 => Some(Self::Column),
+            3 => Some(Self::V),
+            4 => Some(Self::Grid),
+            _ => None,
+        }
+    }
+
+    // Position of the i-th out of `count` enemies relative to the
+    // formation anchor. The formation is built upwards, so the whole
+    // formation starts above the display.
+    fn offset(self, i: usize, count: usize, spacing: i32) -> Point {
+        let i = i as i32;
+        let count = count as i32;
+        let (x, y) = match self {
+            Self::Single => (0, 0),
+            Self::Line => ((2 * i - (count - 1)) * spacing / 2, 0),
+            Self::Column => (0, -i * spacing),
+            Self::V => {
+                let rank = (i + 1) / 2;
+                let side = if i % 2 == 0 { 1 } else { -1 };
+                (side * rank * spacing / 2, -rank * spacing / 2)
+            }
+            Self::Grid => {
+                let mut columns = 1;
+                while columns * columns < count {
+                    columns += 1;
+                }
+                let row = i / columns;
+                let column = i % columns;
+                ((2 * column - (columns - 1)) * spacing / 2, -row * spacing)
+            }
+        };
+        Point::new(x as f32, y as f32)
+    }
+}
+
+const _: () = assert!(Formation::from_byte(level::FORMATION_NAMES.len() as u8).is_none());
+const _: () = assert!(Formation::from_byte(level::FORMATION_NAMES.len() as u8 - 1).is_some());
+
+#[derive(Clone, Copy)]
+enum Path {
+    // Keeps the movement of the enemy kind
+    Default,
+    Straight,
+    ZigZag,
+    Chase,
+    SwoopLeft,
+    SwoopRight,
+    Snake,
+    Orbit,
+}
+
+impl Path {
+    // Follows the order of level::PATH_NAMES
+    const fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Default),
+            1 => Some(Self::Straight),
+            2 => Some(Self::ZigZag),
+            3 => Some(Self::Chase),
+            4 => Some(Self::SwoopLeft),
+            5 => Some(Self::SwoopRight),
+            6 => Some(Self::Snake),
+            7 => Some(Self::Orbit),
+            _ => None,
+        }
+    }
+
+    const fn movement(self) -> Option<Movement> {
+        match self {
+            Self::Default => None,
+            Self::Straight => Some(Movement::Straight),
+            Self::ZigZag => Some(Movement::ZigZag {
+                amplitude: 100.0,
+                frequency: 0.5,
+            }),
+            Self::Chase => Some(Movement::Chase {
+                speed: ENEMY_SPEED / 2,
+            }),
+            Self::SwoopLeft => Some(Movement::Bezier {
+                points: &SWOOP_LEFT,
+                duration: 3.0,
+            }),
+            Self::SwoopRight => Some(Movement::Bezier {
+                points: &SWOOP_RIGHT,
+                duration: 3.0,
+            }),
+            Self::Snake => Some(Movement::Spline {
+                points: &SNAKE,
+                duration: 5.0,
+            }),
+            Self::Orbit => Some(Movement::Orbit {
+                radius: 120.0,
+                angular_speed: PI,
+            }),
+        }
+    }
+}
+
+const _: () = assert!(Path::from_byte(level::PATH_NAMES.len() as u8).is_none());
+const _: () = assert!(Path::from_byte(level::PATH_NAMES.len() as u8 - 1).is_some());
+
+struct Wave {
+    delay: Seconds,
+    kind: EnemyKind,
+    x: i32,
+    formation: Formation,
+    count: usize,
+    path: Path,
+}
+
+impl Wave {
+    fn decode(record: &[u8]) -> Option<Self> {
+        match *record {
+            [delay, kind, x, formation, count, path] => Some(Self {
+                delay: delay as Seconds * level::WAVE_DELAY_UNIT,
+                kind: *ENEMY_KINDS.get(kind as usize)?,
+                x: x as i32 * DISPLAY_WIDTH as i32 / 255,
+                formation: Formation::from_byte(formation)?,
+                count: count as usize,
+                path: Path::from_byte(path)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Plays the waves from LEVEL one after another. See `./level.rs` for
+// the format.
+struct Sequencer {
+    cursor: usize,
+    time: Seconds,
+}
+
+impl Sequencer {
+    const fn new() -> Self {
+        Self {
+            cursor: 0,
+            time: 0.0,
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.cursor >= LEVEL.len()
+    }
+
+    fn update(&mut self, dt: Seconds) {
+        self.time += dt;
+    }
+
+    // Returns the next wave if it is time to spawn it. A malformed
+    // level is treated as finished.
+    fn poll(&mut self) -> Option<Wave> {
+        if self.cursor == 0 {
+            self.cursor = if LEVEL.get(0..level::MAGIC.len()) == Some(&level::MAGIC[..]) {
+                level::MAGIC.len()
+            } else {
+                error!("Unexpected level format, skipping the scripted waves");
+                LEVEL.len()
+            };
+        }
+
+        let wave = LEVEL.get(self.cursor..self.cursor + level::WAVE_RECORD_SIZE).and_then(Wave::decode);
+        match wave {
+            Some(wave) => {
+                if self.time >= wave.delay {
+                    self.time -= wave.delay;
+                    self.cursor += level::WAVE_RECORD_SIZE;
+                    Some(wave)
+                } else {
+                    None
+                }
+            }
+            None => {
+                if self.cursor < LEVEL.len() {
+                    error!("Malformed wave at byte {} of the level, skipping the rest of it", self.cursor);
+                }
+                info!("Scripted waves are over, entering the endless mode");
+                self.cursor = LEVEL.len();
+                None
+            }
+        }
+    }
+}
+
+fn closest_enemy<'a>(enemies: &'a [Enemy], entity: &Entity) -> Option<&'a Entity> {
+    let mut closest: Option<(&Entity, i32)> = None;
+    for enemy in enemies.iter() {
+        if enemy.entity.alive {
+            let distance = (enemy.entity.x - entity.x).abs() + (enemy.entity.y - entity.y).abs();
+            match closest {
+                Some((_, closest_distance)) if closest_distance <= distance => {}
+                _ => closest = Some((&enemy.entity, distance)),
+            }
+        }
+    }
+    closest.map(|(enemy, _)| enemy)
+}
+
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// Splits the text into the lines of at most `width` characters. Lines
+// are broken at '\n' and between the words, unless a single word does
+// not fit.
+struct Lines<'a> {
+    text: &'a [u8],
+    width: usize,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.text.is_empty() {
+            return None;
+        }
+
+        let end = self.text.iter().position(|b| *b == b'\n').unwrap_or(self.text.len());
+        // The separator (newline or space) is dropped
+        let (length, skip) = if end <= self.width {
+            (end, 1)
+        } else {
+            match self.text.get(0..=self.width).and_then(|head| head.iter().rposition(|b| *b == b' ')) {
+                Some(space) if space > 0 => (space, 1),
+                _ => (self.width, 0),
+            }
+        };
+        let (line, rest) = self.text.split_at(length);
+        self.text = rest.get(skip..).unwrap_or(&[]);
+        Some(line)
+    }
+}
+
+struct Font {
+    pixels: [u8; FONT_IMAGE_WIDTH * FONT_IMAGE_HEIGHT],
+}
+
+impl Font {
+    fn decompress_from_bytes(&mut self, bytes: &[u8]) {
+        let n = bytes.len();
+        let mut i = 0;
+        let mut pixels_size: usize = 0;
+        while i < n {
+            if let Some(byte) = bytes.get(i).cloned() {
+                if byte == 0x00 {
+                    i += 1;
+                    if let Some(next_byte) = bytes.get(i).cloned() {
+                        pixels_size += next_byte as usize * 8;
+                    } else {
+                        break;
+                    }
+                    i += 1;
+                } else {
+                    for bit_index in 0..BITS_IN_BYTE {
+                        if let Some(pixel_ref) = self.pixels.get_mut(pixels_size) {
+                            *pixel_ref = ((byte >> (BITS_IN_BYTE - bit_index - 1)) & 1) * 0xFF;
+                        } else {
+                            break;
+                        }
+                        pixels_size += 1;
+                    }
+                    i += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn get(&self, x: i32, y: i32) -> Option<&u8> {
+        self.pixels.get(y as usize * FONT_IMAGE_WIDTH + x as usize)
+    }
+
+    // Calls `put` with the offset of every lit pixel of the scaled
+    // character
+    fn glyph<F: FnMut(i32, i32)>(&self, code: u8, scale: i32, mut put: F) {
+        if 32 <= code && code <= 126 {
+            let char_x = (code - 32) as usize % FONT_IMAGE_COLS;
+            let char_y = (code - 32) as usize / FONT_IMAGE_COLS;
+
+            for y in 0..FONT_CHAR_HEIGHT as i32 {
+                for x in 0..FONT_CHAR_WIDTH as i32 {
+                    for scale_x in 0..scale {
+                        for scale_y in 0..scale {
+                            let font_x = char_x as i32 * FONT_CHAR_WIDTH as i32 + x;
+                            let font_y = char_y as i32 * FONT_CHAR_HEIGHT as i32 + y;
+
+                            if let Some(alpha) = self.get(font_x, font_y) {
+                                if *alpha == 0xFF {
+                                    put(x * scale + scale_x, y * scale + scale_y);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        } else {
+            self.glyph('?' as u8, scale, put)
+        }
+    }
+
+    fn render_ascii(&self,
+                    display: &mut Display,
+                    code: u8,
+                    start_x: i32, start_y: i32,
+                    scale: i32,
+                    color: Pixel) {
+        self.glyph(code, scale, |x, y| display.put(start_x + x, start_y + y, color));
+    }
+
+    fn render_bytes(&self,
+                    display: &mut Display,
+                    bytes: &[u8],
+                    x: i32, y: i32,
+                    scale: i32,
+                    color: Pixel) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.render_ascii(
+                display,
+                *byte,
+                x + i as i32 * FONT_CHAR_WIDTH as i32 * scale, y,
+                scale,
+                color);
+        }
+    }
+
+    fn line_height(&self, scale: i32) -> i32 {
+        (FONT_CHAR_HEIGHT as i32 + TEXT_LINE_SPACING) * scale
+    }
+
+    fn lines<'a>(&self, text: &'a [u8], scale: i32, width: i32) -> Lines<'a> {
+        Lines {
+            text,
+            width: max(width / (FONT_CHAR_WIDTH as i32 * scale), 1) as usize,
+        }
+    }
+
+    // Width and height of the text wrapped to the width
+    fn measure(&self, text: &[u8], scale: i32, width: i32) -> (i32, i32) {
+        let mut size = (0, 0);
+        for line in self.lines(text, scale, width) {
+            size.0 = max(size.0, FONT_CHAR_WIDTH as i32 * scale * line.len() as i32);
+            size.1 += self.line_height(scale);
+        }
+        if size.1 > 0 {
+            size.1 -= TEXT_LINE_SPACING * scale;
+        }
+        size
+    }
+
+    // Lays the text out from the top of the rect, wrapping it to the
+    // width and aligning every line. Whatever does not fit into the
+    // rect is cut off.
+    fn render_text(&self,
+                   display: &mut Display,
+                   text: &[u8],
+                   rect: Rect,
+                   scale: i32,
+                   align: Align,
+                   color: Pixel) {
+        for (index, line) in self.lines(text, scale, rect.w).enumerate() {
+            let y = rect.y + index as i32 * self.line_height(scale);
+            if y >= rect.y + rect.h {
+                break;
+            }
+            let width = FONT_CHAR_WIDTH as i32 * scale * line.len() as i32;
+            let x = match align {
+                Align::Left => rect.x,
+                Align::Center => rect.x + (rect.w - width) / 2,
+                Align::Right => rect.x + rect.w - width,
+            };
+            let shadow = (-SHADOW_OFFSET, SHADOW_COLOR.with_alpha(color.alpha()));
+            for &(offset, color) in [shadow, (0, color)].iter() {
+                // The shadow is clipped as if it was cast by the clipped text
+                let clip = Rect::new(rect.x + offset, rect.y + offset, rect.w, rect.h);
+                for (i, byte) in line.iter().enumerate() {
+                    let char_x = x + offset + i as i32 * FONT_CHAR_WIDTH as i32 * scale;
+                    let char_y = y + offset;
+                    self.glyph(*byte, scale, |x, y| {
+                        if clip.contains(char_x + x, char_y + y) {
+                            display.put(char_x + x, char_y + y, color);
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+const LABEL_CAPACITY: usize = 64;
+type Label = FixedBuffer<LABEL_CAPACITY>;
+
+impl Label {
+    fn render(&self,
+              display: &mut Display,
+              font: &Font,
+              x: i32, y: i32,
+              scale: i32,
+              color: Pixel) {
+        let bytes = self.as_bytes();
+        font.render_bytes(display, bytes, x - SHADOW_OFFSET, y - SHADOW_OFFSET, scale,
+                          SHADOW_COLOR.with_alpha(color.alpha()));
+        font.render_bytes(display, bytes, x, y, scale, color);
+    }
+
+    // Width of the label rendered with the scale
+    fn width(&self, font: &Font, scale: i32) -> i32 {
+        font.measure(self.as_bytes(), scale, TEXT_NO_WRAP).0
+    }
+}
+
+struct Game {
+    player: Entity,
+    lives: usize,
+    invulnerability: Seconds,
+    bullets: [Bullet; BULLETS_CAPACITY],
+    enemies: [Enemy; ENEMIES_CAPACITY],
+    enemy_bullets: [EnemyBullet; ENEMY_BULLETS_CAPACITY],
+    pickups: [Pickup; PICKUPS_CAPACITY],
+    particles: [Particle; PARTICLES_CAPACITY],
+    shield: Seconds,
+    score_multiplier: Seconds,
+    slow_motion: Seconds,
+    sequencer: Sequencer,
+    enemy_spawn_cooldown: Seconds,
+    boss_cooldown: Seconds,
+    weapon: WeaponKind,
+    weapon_levels: [usize; WEAPONS_COUNT],
+    weapon_cooldown: Seconds,
+    trigger: bool,
+    difficulty: Difficulty,
+    score: usize,
+    // How long the player has survived
+    time: Seconds,
+    // Kills in a row without missing or getting hit
+    combo: usize,
+    combo_timer: Seconds,
+    score_label: Label,
+    time_label: Label,
+    combo_label: Label,
+    weapon_label: Label,
+    effects_label: Label,
+    rng: Rng,
+    // For the effects that do not affect the gameplay, so the same
+    // seed always produces the same game
+    cosmetic_rng: Rng,
+}
+
+impl Game {
+    const fn default() -> Self {
+        Self {
+            player: Entity::new(0, DISPLAY_HEIGHT as i32 - PLAYER_SIZE),
+            lives: PLAYER_LIVES,
+            invulnerability: 0.0,
+            bullets: [Bullet::dead(); BULLETS_CAPACITY],
+            enemies: [Enemy::dead(); ENEMIES_CAPACITY],
+            enemy_bullets: [EnemyBullet::dead(); ENEMY_BULLETS_CAPACITY],
+            pickups: [Pickup::dead(); PICKUPS_CAPACITY],
+            particles: [Particle::dead(); PARTICLES_CAPACITY],
+            shield: 0.0,
+            score_multiplier: 0.0,
+            slow_motion: 0.0,
+            sequencer: Sequencer::new(),
+            enemy_spawn_cooldown: 0.0,
+            boss_cooldown: BOSS_PERIOD,
+            weapon: WeaponKind::Blaster,
+            weapon_levels: [0; WEAPONS_COUNT],
+            weapon_cooldown: 0.0,
+            trigger: false,
+            difficulty: Difficulty::Normal,
+            score: 0,
+            time: 0.0,
+            combo: 0,
+            combo_timer: 0.0,
+            score_label: Label::new(),
+            time_label: Label::new(),
+            combo_label: Label::new(),
+            weapon_label: Label::new(),
+            effects_label: Label::new(),
+            rng: Rng::new(RNG_DEFAULT_SEED, RNG_GAMEPLAY_STREAM),
+            cosmetic_rng: Rng::new(RNG_DEFAULT_SEED, RNG_COSMETIC_STREAM),
+        }
+    }
+
+    fn update(&mut self, dt: Seconds, audio: &mut audio::Audio, popups: &mut Popups) {
+        // Everything but the player and their bullets is slowed
+        // down by the Slow Motion pickup
+        let world_dt = if self.slow_motion > 0.0 {
+            dt * SLOW_MOTION_FACTOR
+        } else {
+            dt
+        };
+
+        self.invulnerability -= dt;
+        self.shield -= dt;
+        self.score_multiplier -= dt;
+        self.slow_motion -= dt;
+        if self.player.alive {
+            self.time += dt;
+        }
+        self.combo_timer -= dt;
+        if self.combo_timer <= 0.0 {
+            self.combo = 0;
+        }
+
+        let mut missed = false;
+        for bullet in self.bullets.iter_mut() {
+            if bullet.entity.alive {
+                let weapon = bullet.kind.weapon();
+
+                if weapon.homing {
+                    if let Some(target) = closest_enemy(&self.enemies, &bullet.entity) {
+                        let speed = weapon.speed as f32;
+                        let dx = (target.x - bullet.entity.x) as f32;
+                        let dy = (target.y - bullet.entity.y) as f32;
+                        bullet.vx = (bullet.vx + dx.signum() * HOMING_TURN_SPEED * dt).clamp(-speed, speed);
+                        bullet.vy = (bullet.vy + dy.signum() * HOMING_TURN_SPEED * dt).clamp(-speed, speed);
+                    }
+                }
+
+                bullet.entity.x += (bullet.vx * dt) as i32;
+                bullet.entity.y += (bullet.vy * dt) as i32;
+                if bullet.entity.x + weapon.size / 2 < 0
+                    || bullet.entity.x - weapon.size / 2 > DISPLAY_WIDTH as i32
+                    || bullet.entity.y + weapon.size / 2 < 0
+                    || bullet.entity.y - weapon.size / 2 > DISPLAY_HEIGHT as i32
+                {
+                    bullet.entity.alive = false;
+                    missed |= bullet.hits == 0;
+                }
+            }
+        }
+        if missed {
+            self.combo = 0;
+        }
+
+        for bullet_index in 0..BULLETS_CAPACITY {
+            let trail = match self.bullets.get(bullet_index) {
+                Some(bullet) if bullet.entity.alive && bullet.kind.weapon().trail => {
+                    Some((bullet.entity.x, bullet.entity.y, bullet.kind.weapon().color))
+                }
+                _ => None,
+            };
+            if let Some((x, y, color)) = trail {
+                self.emit(x, y, &Emitter { color, ..TRAIL_EMITTER });
+            }
+        }
+
+        // Enemies are iterated by index so killing one can
+        // mutate the rest of the State (drop pickups, etc)
+        for enemy_index in 0..ENEMIES_CAPACITY {
+            let enemy_bit = 1 << enemy_index;
+            let mut killed = false;
+            let mut fire = false;
+            if let Some(enemy) = self.enemies.get_mut(enemy_index) {
+                if enemy.entity.alive {
+                    enemy.update(world_dt, self.player.x);
+                    fire = enemy.reload(world_dt);
+
+                    // Check if any bullets killed the enemy by
+                    // overlaping with it
+                    for bullet in self.bullets.iter_mut() {
+                        if bullet.entity.alive && bullet.hits & enemy_bit == 0 {
+                            let weapon = bullet.kind.weapon();
+                            if enemy.entity.overlaps(enemy.size(), &bullet.entity, weapon.size) {
+                                bullet.hits |= enemy_bit;
+                                if !weapon.pierce {
+                                    bullet.entity.alive = false;
+                                }
+                                enemy.health -= weapon.damage;
+                                if enemy.health <= 0 {
+                                    enemy.entity.alive = false;
+                                    killed = true;
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            if killed {
+                self.enemy_killed(enemy_index, audio, popups);
+            } else if fire && self.player.alive {
+                self.enemy_fire(enemy_index);
+            }
+        }
+
+        for bullet in self.enemy_bullets.iter_mut() {
+            if bullet.entity.alive {
+                bullet.update(world_dt);
+            }
+        }
+
+        // Enemies could be killed by bullets in the previous
+        // loop. So only the survivors can hit the player.
+        if self.player.alive && self.invulnerability <= 0.0 {
+            for enemy_index in 0..ENEMIES_CAPACITY {
+                let contact = match self.enemies.get_mut(enemy_index) {
+                    Some(enemy) if enemy.entity.alive && enemy.entity.overlaps(enemy.size(), &self.player, PLAYER_SIZE) => {
+                        // Nothing but bullets can take down the boss
+                        if enemy.kind != EnemyKind::Boss {
+                            enemy.entity.alive = false;
+                        }
+                        Some(enemy.entity.alive)
+                    }
+                    _ => None,
+                };
+                if let Some(survived) = contact {
+                    // The shield destroys anything that touches it
+                    if !survived && self.shield > 0.0 {
+                        self.enemy_killed(enemy_index, audio, popups);
+                    }
+                    self.player_hit(audio);
+                }
+            }
+
+            for bullet_index in 0..ENEMY_BULLETS_CAPACITY {
+                let hit = match self.enemy_bullets.get_mut(bullet_index) {
+                    Some(bullet) if bullet.entity.alive && bullet.entity.overlaps(ENEMY_BULLET_SIZE, &self.player, PLAYER_SIZE) => {
+                        bullet.entity.alive = false;
+                        true
+                    }
+                    _ => false,
+                };
+                if hit {
+                    self.player_hit(audio);
+                }
+            }
+        }
+
+        for particle in self.particles.iter_mut() {
+            if particle.alive {
+                particle.update(world_dt);
+            }
+        }
+
+        for pickup in self.pickups.iter_mut() {
+            if pickup.entity.alive {
+                pickup.entity.y += (PICKUP_SPEED as f32 * world_dt) as i32;
+                if pickup.entity.y - PICKUP_SIZE / 2 > DISPLAY_HEIGHT as i32 {
+                    pickup.entity.alive = false;
+                }
+            }
+        }
+
+        for pickup_index in 0..PICKUPS_CAPACITY {
+            let collected = match self.pickups.get_mut(pickup_index) {
+                Some(pickup) if pickup.entity.alive && self.player.alive
+                    && pickup.entity.overlaps(PICKUP_SIZE, &self.player, PLAYER_SIZE) => {
+                    pickup.entity.alive = false;
+                    Some(pickup.kind)
+                }
+                _ => None,
+            };
+            if let Some(kind) = collected {
+                self.collect_pickup(kind, audio, popups);
+            }
+        }
+
+        // Keep shooting while the trigger is held
+        self.weapon_cooldown -= dt;
+        if self.trigger {
+            self.shoot(audio);
+        }
+
+        // Normal spawning is suspended during the boss fight
+        if self.boss().is_none() {
+            if !self.sequencer.finished() {
+                self.sequencer.update(world_dt);
+                while let Some(wave) = self.sequencer.poll() {
+                    self.spawn_wave(&wave);
+                }
+            } else {
+                // The script is over, keep spawning random
+                // enemies endlessly
+                self.boss_cooldown -= world_dt;
+                if self.boss_cooldown < 0.0
+                    && self.spawn_enemy(EnemyKind::Boss, DISPLAY_WIDTH as i32 / 2, -BOSS_SIZE / 2).is_some() {
+                    self.boss_cooldown = BOSS_PERIOD;
+                }
+
+                self.enemy_spawn_cooldown -= world_dt;
+                if self.enemy_spawn_cooldown < 0.0 {
+                    let kind = EnemyKind::random(&mut self.rng);
+                    let enemy_x = self.rng.range(DISPLAY_WIDTH as i32);
+                    self.spawn_enemy(kind, enemy_x, -kind.class().size / 2);
+                    self.enemy_spawn_cooldown = ENEMY_SPAWN_PERIOD * self.difficulty.spawn_period();
+                }
+            }
+        }
+
+        self.score_label.clear();
+        self.score_label.push_bytes(b"Score: ");
+        self.score_label.push_grouped(self.score);
+
+        self.time_label.clear();
+        self.time_label.push_timer(self.time);
+
+        self.combo_label.clear();
+        if self.combo_multiplier() > 1 {
+            let _ = write!(self.combo_label, "Combo x{}", self.combo_multiplier());
+        }
+
+        self.weapon_label.clear();
+        self.weapon_label.push_bytes(self.weapon.weapon().name);
+        self.weapon_label.push_bytes(b" Lv.");
+        self.weapon_label.push_usize(self.weapon_level() + 1);
+
+        self.effects_label.clear();
+        for (name, left) in [(&b"Shield"[..], self.shield),
+                             (&b"x2"[..], self.score_multiplier),
+                             (&b"Slow"[..], self.slow_motion)].iter() {
+            if *left > 0.0 {
+                self.effects_label.push_bytes(name);
+                self.effects_label.push_byte(b' ');
+                self.effects_label.push_usize(*left as usize + 1);
+                self.effects_label.push_byte(b' ');
+            }
+        }
+    }
+
+    fn render(&self, display: &mut Display, font: &Font) {
+        display.fill(DISPLAY_BACKGROUND);
+        for pickup in self.pickups.iter() {
+            pickup.render(display, font);
+        }
+        // The player blinks while they are invulnerable
+        let blink = self.invulnerability > 0.0
+            && (self.invulnerability / PLAYER_BLINK_PERIOD) as i32 % 2 == 0;
+        if !blink {
+            if self.shield > 0.0 {
+                self.player.render(display, SHIELD_SIZE, SHIELD_COLOR);
+            }
+            self.player.render(display, PLAYER_SIZE, PLAYER_COLOR);
+        }
+        for bullet in self.bullets.iter() {
+            bullet.render(display)
+        }
+        for enemy in self.enemies.iter() {
+            enemy.render(display)
+        }
+        for bullet in self.enemy_bullets.iter() {
+            bullet.entity.render(display, ENEMY_BULLET_SIZE, ENEMY_BULLET_COLOR)
+        }
+        for particle in self.particles.iter() {
+            particle.render(display)
+        }
+        if let Some(boss) = self.boss() {
+            let width = BOSS_BAR_WIDTH * max(boss.health, 0) / BOSS_HEALTH;
+            display.fill_rect(BOSS_BAR_X - SHADOW_OFFSET, BOSS_BAR_Y - SHADOW_OFFSET,
+                              BOSS_BAR_WIDTH, BOSS_BAR_HEIGHT, SHADOW_COLOR);
+            display.fill_rect(BOSS_BAR_X, BOSS_BAR_Y, BOSS_BAR_WIDTH, BOSS_BAR_HEIGHT, BOSS_BAR_BACKGROUND);
+            display.fill_rect(BOSS_BAR_X, BOSS_BAR_Y, width, BOSS_BAR_HEIGHT, BOSS_BAR_COLOR);
+        }
+        self.score_label.render(display, font,
+                                SCORE_LABEL_X,
+                                SCORE_LABEL_Y,
+                                4,
+                                SCORE_LABEL_COLOR);
+        self.time_label.render(display, font,
+                               DISPLAY_WIDTH as i32 - LIFE_ICON_PADDING - self.time_label.width(font, WEAPON_LABEL_SCALE),
+                               TIME_LABEL_Y,
+                               WEAPON_LABEL_SCALE,
+                               SCORE_LABEL_COLOR);
+        // Next to the score, aligned to the bottom of it
+        self.combo_label.render(display, font,
+                                SCORE_LABEL_X + self.score_label.width(font, 4) + FONT_CHAR_WIDTH as i32 * 4,
+                                SCORE_LABEL_Y + FONT_CHAR_HEIGHT as i32 * (4 - WEAPON_LABEL_SCALE),
+                                WEAPON_LABEL_SCALE,
+                                COMBO_LABEL_COLOR);
+        self.weapon_label.render(display, font,
+                                 SCORE_LABEL_X,
+                                 WEAPON_LABEL_Y,
+                                 WEAPON_LABEL_SCALE,
+                                 SCORE_LABEL_COLOR);
+        let effects_rect = Rect::new(SCORE_LABEL_X, EFFECTS_LABEL_Y,
+                                     EFFECTS_LABEL_WIDTH, DISPLAY_HEIGHT as i32 - EFFECTS_LABEL_Y);
+        font.render_text(display, self.effects_label.as_bytes(), effects_rect,
+                         WEAPON_LABEL_SCALE, Align::Left, SCORE_LABEL_COLOR);
+        for i in 0..self.lives as i32 {
+            let x = DISPLAY_WIDTH as i32 - (i + 1) * (LIFE_ICON_SIZE + LIFE_ICON_PADDING);
+            let y = LIFE_ICON_PADDING + LIFE_ICON_SIZE / 2 + SHADOW_OFFSET;
+            Entity::new(x, y).render(display, LIFE_ICON_SIZE, PLAYER_COLOR);
+        }
+    }
+
+    fn spawn_enemy(&mut self, kind: EnemyKind, x: i32, y: i32) -> Option<&mut Enemy> {
+        // The last free slot is kept for the boss, so the boss fight
+        // is never skipped because of the crowd
+        let free = self.enemies.iter().filter(|enemy| !enemy.entity.alive).count();
+        if kind != EnemyKind::Boss && free <= 1 {
+            warn!("All {} enemy slots are taken, skipping the spawn", ENEMIES_CAPACITY);
+            return None;
+        }
+
+        for enemy in self.enemies.iter_mut() {
+            if !enemy.entity.alive {
+                let class = kind.class();
+                enemy.entity.revive(x, y);
+                enemy.kind = kind;
+                enemy.health = class.health;
+                enemy.age = 0.0;
+                enemy.origin = Point::new(x as f32, y as f32);
+                enemy.offset = Point::new(0.0, 0.0);
+                enemy.movement = class.movement;
+                enemy.fire_cooldown = class.fire.period();
+                if kind == EnemyKind::Boss {
+                    info!("Boss fight started");
+                }
+                return Some(enemy);
+            }
+        }
+        warn!("All {} enemy slots are taken, skipping the spawn", ENEMIES_CAPACITY);
+        None
+    }
+
+    fn spawn_wave(&mut self, wave: &Wave) {
+        let size = wave.kind.class().size;
+        let count = match wave.formation {
+            Formation::Single => 1,
+            _ => wave.count,
+        };
+
+        for i in 0..count {
+            let offset = wave.formation.offset(i, count, size * WAVE_SPACING / 2);
+            if let Some(enemy) = self.spawn_enemy(wave.kind, wave.x, -size / 2) {
+                enemy.offset = offset;
+                if let Some(movement) = wave.path.movement() {
+                    enemy.movement = movement;
+                }
+                enemy.place();
+            }
+        }
+    }
+
+    fn spawn_pickup(&mut self, x: i32, y: i32, kind: PickupKind) {
+        for pickup in self.pickups.iter_mut() {
+            if !pickup.entity.alive {
+                pickup.entity.revive(x, y);
+                pickup.kind = kind;
+                break;
+            }
+        }
+    }
+
+    fn enemy_killed(&mut self, enemy_index: usize, audio: &mut audio::Audio, popups: &mut Popups) {
+        let (kind, x, y) = match self.enemies.get(enemy_index) {
+            Some(enemy) => (enemy.kind, enemy.entity.x, enemy.entity.y),
+            None => return,
+        };
+
+        let multiplier = if self.score_multiplier > 0.0 {
+            SCORE_MULTIPLIER
+        } else {
+            1
+        };
+        self.combo += 1;
+        self.combo_timer = COMBO_WINDOW;
+        let class = kind.class();
+        let reward = class.reward * multiplier * self.combo_multiplier();
+        self.score += reward;
+        if let Some(label) = popups.spawn(x, y, SCORE_POPUP_LIFETIME, SCORE_LABEL_COLOR) {
+            let _ = write!(label, "+{}", reward);
+        }
+        let combo = self.combo;
+        if 1 < combo && combo <= COMBO_MAX_MULTIPLIER {
+            if let Some(label) = popups.spawn(x, y - COMBO_POPUP_OFFSET, COMBO_POPUP_LIFETIME, COMBO_LABEL_COLOR) {
+                let _ = write!(label, "COMBO x{}", combo);
+            }
+        }
+
+        audio.play(&audio::EXPLOSION);
+
+        // Bigger enemies make bigger explosions
+        self.emit(x, y, &Emitter {
+            count: EXPLOSION_EMITTER.count * class.size as usize / ENEMY_SIZE as usize,
+            speed: EXPLOSION_EMITTER.speed * class.size as f32 / ENEMY_SIZE as f32,
+            color: class.color,
+            ..EXPLOSION_EMITTER
+        });
+
+        if kind == EnemyKind::Splitter {
+            self.spawn_enemy(EnemyKind::Splitling, x - SPLITLING_OFFSET, y);
+            self.spawn_enemy(EnemyKind::Splitling, x + SPLITLING_OFFSET, y);
+        }
+
+        // The boss always leaves a weapon upgrade behind
+        if kind == EnemyKind::Boss {
+            info!("Boss defeated");
+            self.spawn_pickup(x, y, PickupKind::WeaponUpgrade);
+        }
+
+        if self.rng.range(100) < PICKUP_DROP_CHANCE {
+            let kind = PickupKind::random(&mut self.rng);
+            self.spawn_pickup(x, y, kind);
+        }
+    }
+
+    fn combo_multiplier(&self) -> usize {
+        clamp(self.combo as i32, 1, COMBO_MAX_MULTIPLIER as i32) as usize
+    }
+
+    fn emit(&mut self, x: i32, y: i32, emitter: &Emitter) {
+        let mut count = emitter.count;
+        for particle in self.particles.iter_mut() {
+            if count == 0 {
+                break;
+            }
+            if !particle.alive {
+                let angle = emitter.angle + emitter.spread * (self.cosmetic_rng.unit() - 0.5);
+                // Vary the speed so the burst does not look like a ring
+                let speed = emitter.speed * (0.25 + 0.75 * self.cosmetic_rng.unit());
+                particle.alive = true;
+                particle.position = Point::new(x as f32, y as f32);
+                particle.velocity = Point::new(cos(angle) * speed, sin(angle) * speed);
+                particle.age = 0.0;
+                particle.lifetime = emitter.lifetime * (0.5 + 0.5 * self.cosmetic_rng.unit());
+                particle.size = emitter.size;
+                particle.color = emitter.color;
+                count -= 1;
+            }
+        }
+    }
+
+    fn player_hit(&mut self, audio: &mut audio::Audio) {
+        if self.shield <= 0.0 && self.invulnerability <= 0.0 {
+            self.emit(self.player.x, self.player.y, &PLAYER_HIT_EMITTER);
+            audio.play(&audio::HIT);
+            self.combo = 0;
+            self.lives = self.lives.saturating_sub(1);
+            if self.lives == 0 {
+                self.player.alive = false;
+            } else {
+                self.invulnerability = PLAYER_INVULNERABILITY_PERIOD;
+            }
+        }
+    }
+
+    fn spawn_enemy_bullet(&mut self, x: i32, y: i32, vx: f32, vy: f32) {
+        for bullet in self.enemy_bullets.iter_mut() {
+            if !bullet.entity.alive {
+                bullet.entity.revive(x, y);
+                bullet.vx = vx;
+                bullet.vy = vy;
+                break;
+            }
+        }
+    }
+
+    fn boss(&self) -> Option<&Enemy> {
+        self.enemies.iter().find(|enemy| enemy.entity.alive && enemy.kind == EnemyKind::Boss)
+    }
+
+    fn enemy_fire(&mut self, enemy_index: usize) {
+        let (x, y, age, fire) = match self.enemies.get(enemy_index) {
+            Some(enemy) => (enemy.entity.x, enemy.entity.y, enemy.age, enemy.fire()),
+            None => return,
+        };
+
+        match fire {
+            Fire::Never => {}
+            Fire::Aimed { speed, .. } => {
+                let dx = (self.player.x - x) as f32;
+                let dy = (self.player.y - y) as f32;
+                let length = sqrt(dx * dx + dy * dy);
+                if length > 0.0 {
+                    let speed = speed as f32;
+                    self.spawn_enemy_bullet(x, y, dx / length * speed, dy / length * speed);
+                }
+            }
+            Fire::Radial { count, speed, .. } => {
+                let speed = speed as f32;
+                for i in 0..count {
+                    let angle = 2.0 * PI * i as f32 / count as f32;
+                    self.spawn_enemy_bullet(x, y, cos(angle) * speed, sin(angle) * speed);
+                }
+            }
+            Fire::Spiral { speed, spin, .. } => {
+                let speed = speed as f32;
+                let angle = spin * age;
+                self.spawn_enemy_bullet(x, y, cos(angle) * speed, sin(angle) * speed);
+            }
+        }
+    }
+
+    fn collect_pickup(&mut self, kind: PickupKind, audio: &mut audio::Audio, popups: &mut Popups) {
+        debug!("Collected pickup {}", kind.letter() as char);
+        audio.play(&audio::PICKUP);
+        let (x, y) = (self.player.x, self.player.y - PLAYER_SIZE);
+        if let Some(label) = popups.spawn(x, y, PICKUP_POPUP_LIFETIME, kind.color()) {
+            label.push_bytes(kind.popup());
+        }
+        match kind {
+            PickupKind::ExtraLife => {
+                if self.lives < PLAYER_MAX_LIVES {
+                    self.lives += 1;
+                }
+            }
+            PickupKind::Shield => self.shield = SHIELD_PERIOD,
+            PickupKind::WeaponUpgrade => self.upgrade_weapon(),
+            PickupKind::ScoreMultiplier => self.score_multiplier = SCORE_MULTIPLIER_PERIOD,
+            PickupKind::SlowMotion => self.slow_motion = SLOW_MOTION_PERIOD,
+        }
+    }
+
+    fn spawn_bullet(&mut self, x: i32, y: i32, kind: WeaponKind, vx: f32, vy: f32) {
+        for bullet in self.bullets.iter_mut() {
+            if !bullet.entity.alive {
+                bullet.entity.revive(x, y);
+                bullet.kind = kind;
+                bullet.vx = vx;
+                bullet.vy = vy;
+                bullet.hits = 0;
+                break;
+            }
+        }
+    }
+
+    fn weapon_level(&self) -> usize {
+        self.weapon_levels.get(self.weapon as usize).cloned().unwrap_or(0)
+    }
+
+    fn select_weapon(&mut self, index: usize) {
+        if let Some(kind) = WeaponKind::from_index(index) {
+            if kind != self.weapon {
+                debug!("Switched to weapon {}", index + 1);
+                self.weapon = kind;
+                self.weapon_cooldown = 0.0;
+            }
+        }
+    }
+
+    fn upgrade_weapon(&mut self) {
+        if let Some(level) = self.weapon_levels.get_mut(self.weapon as usize) {
+            if *level < WEAPON_MAX_LEVEL {
+                *level += 1;
+            }
+        }
+    }
+
+    fn move_player(&mut self, x: i32) {
+        if self.player.alive {
+            self.player.x = x;
+        }
+    }
+
+    fn shoot(&mut self, audio: &mut audio::Audio) {
+        if self.player.alive && self.weapon_cooldown <= 0.0 {
+            let weapon = self.weapon.weapon();
+            let level = self.weapon_level();
+            let n = weapon.projectiles(level);
+            for i in 0..n {
+                // Fan the projectiles out symmetrically around the
+                // vertical axis
+                let vx = (2 * i as i32 - (n as i32 - 1)) as f32 * weapon.spread / 2.0;
+                self.spawn_bullet(
+                    self.player.x,
+                    self.player.y - PLAYER_SIZE / 2 - weapon.size / 2,
+                    self.weapon,
+                    vx,
+                    -weapon.speed as f32,
+                );
+            }
+            audio.play(&audio::SHOOT);
+            self.emit(self.player.x, self.player.y - PLAYER_SIZE / 2, &Emitter {
+                color: weapon.color,
+                ..MUZZLE_FLASH_EMITTER
+            });
+            self.weapon_cooldown = weapon.cooldown(level);
+        }
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed, RNG_GAMEPLAY_STREAM);
+        self.cosmetic_rng = Rng::new(seed, RNG_COSMETIC_STREAM);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+const DIFFICULTY_NAMES: [&[u8]; 3] = [b"Easy", b"Normal", b"Hard"];
+
+impl Difficulty {
+    const fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Self::Easy),
+            1 => Some(Self::Normal),
+            2 => Some(Self::Hard),
+            _ => None,
+        }
+    }
+
+    const fn lives(self) -> usize {
+        match self {
+            Self::Easy => PLAYER_MAX_LIVES,
+            Self::Normal => PLAYER_LIVES,
+            Self::Hard => 1,
+        }
+    }
+
+    // Multiplier of the period between the endless mode spawns
+    const fn spawn_period(self) -> f32 {
+        match self {
+            Self::Easy => 1.5,
+            Self::Normal => 1.0,
+            Self::Hard => 0.6,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Controls {
+    // The player follows the cursor and shoots on click
+    Mouse,
+    // The player is moved with Left and Right and shoots with Confirm
+    Buttons,
+}
+
+const CONTROLS_NAMES: [&[u8]; 2] = [b"Mouse", b"Buttons"];
+
+impl Controls {
+    const fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Self::Mouse),
+            1 => Some(Self::Buttons),
+            _ => None,
+        }
+    }
+}
+
+// Recolors the whole frame after it is rendered
+#[derive(Clone, Copy, PartialEq)]
+enum Palette {
+    Default,
+    Grayscale,
+    Amber,
+    HighContrast,
+}
+
+const PALETTE_NAMES: [&[u8]; 4] = [b"Default", b"Grayscale", b"Amber", b"Contrast"];
+
+impl Palette {
+    const fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Self::Default),
+            1 => Some(Self::Grayscale),
+            2 => Some(Self::Amber),
+            3 => Some(Self::HighContrast),
+            _ => None,
+        }
+    }
+
+    fn apply(self, display: &mut Display) {
+        if self == Self::Default {
+            return;
+        }
+        for pixel_ref in display.pixels.iter_mut() {
+            let r = pixel_ref.0 & 0xFF;
+            let g = (pixel_ref.0 >> 8) & 0xFF;
+            let b = (pixel_ref.0 >> 16) & 0xFF;
+            let luma = ((r * 77 + g * 150 + b * 29) >> 8) as u8;
+            let stretch = |c: u32| clamp((c as i32 - 0x80) * 2 + 0x80, 0, 0xFF) as u8;
+            *pixel_ref = match self {
+                Self::Default => *pixel_ref,
+                Self::Grayscale => Pixel::rgba(luma, luma, luma, 0xFF),
+                Self::Amber => Pixel::rgba(luma, (luma as u32 * 3 / 4) as u8, luma / 4, 0xFF),
+                Self::HighContrast => Pixel::rgba(stretch(r), stretch(g), stretch(b), 0xFF),
+            };
+        }
+    }
+}
+
+// The settings are exchanged with the host as CONFIG_SIZE bytes:
+//   CONFIG_MAGIC,
+//   CONFIG_VERSION,
+//   volume in percents,
+//   index in DIFFICULTY_NAMES,
+//   index in CONTROLS_NAMES,
+//   index in PALETTE_NAMES,
+//   flags (bit 0 is show_fps)
+#[derive(Clone, Copy)]
+struct Config {
+    volume: i32,
+    difficulty: Difficulty,
+    controls: Controls,
+    palette: Palette,
+    show_fps: bool,
+}
+
+impl Config {
+    const fn default() -> Self {
+        Self {
+            volume: 100,
+            difficulty: Difficulty::Normal,
+            controls: Controls::Mouse,
+            palette: Palette::Default,
+            show_fps: false,
+        }
+    }
+
+    fn save(&self, bytes: &mut [u8]) -> usize {
+        let config = [
+            CONFIG_MAGIC[0],
+            CONFIG_MAGIC[1],
+            CONFIG_VERSION,
+            self.volume as u8,
+            self.difficulty as u8,
+            self.controls as u8,
+            self.palette as u8,
+            self.show_fps as u8,
+        ];
+        match bytes.get_mut(0..CONFIG_SIZE) {
+            Some(bytes) => {
+                bytes.copy_from_slice(&config);
+                CONFIG_SIZE
+            }
+            None => 0,
+        }
+    }
+
+    // Fields that do not make sense keep their defaults, so a damaged
+    // config never prevents the game from starting
+    fn load(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [m0, m1, version, volume, difficulty, controls, palette, flags, ..]
+                if [*m0, *m1] == CONFIG_MAGIC && *version == CONFIG_VERSION =>
+            {
+                let default = Self::default();
+                Some(Self {
+                    volume: if *volume <= 100 { *volume as i32 } else { default.volume },
+                    difficulty: Difficulty::from_index(*difficulty as usize).unwrap_or(default.difficulty),
+                    controls: Controls::from_index(*controls as usize).unwrap_or(default.controls),
+                    palette: Palette::from_index(*palette as usize).unwrap_or(default.palette),
+                    show_fps: flags & 1 != 0,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct HighScore {
+    name: [u8; HIGH_SCORE_NAME_LENGTH],
+    score: u32,
+}
+
+// Sorted from the best to the worst. Exchanged with the host as bytes:
+//   HIGH_SCORES_MAGIC,
+//   HIGH_SCORES_VERSION,
+//   amount of the entries,
+//   HIGH_SCORE_RECORD_SIZE byte records of the name in capital letters
+//   followed by the little-endian u32 score
+struct HighScores {
+    entries: [HighScore; HIGH_SCORES_CAPACITY],
+    count: usize,
+}
+
+impl HighScores {
+    const fn empty() -> Self {
+        Self {
+            entries: [HighScore { name: [b'A'; HIGH_SCORE_NAME_LENGTH], score: 0 }; HIGH_SCORES_CAPACITY],
+            count: 0,
+        }
+    }
+
+    fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.count < HIGH_SCORES_CAPACITY || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    // Returns the rank of the new entry, if it made it into the table
+    fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        let rank = self
+            .entries
+            .get(0..self.count)?
+            .iter()
+            .position(|other| entry.score > other.score)
+            .unwrap_or(self.count);
+        if rank >= HIGH_SCORES_CAPACITY {
+            return None;
+        }
+        self.count = min(self.count as i32 + 1, HIGH_SCORES_CAPACITY as i32) as usize;
+        self.entries.get_mut(rank..self.count)?.rotate_right(1);
+        *self.entries.get_mut(rank)? = entry;
+        Some(rank)
+    }
+
+    fn save(&self, bytes: &mut [u8]) -> usize {
+        let size = HIGH_SCORES_HEADER_SIZE + self.count * HIGH_SCORE_RECORD_SIZE;
+        let bytes = match bytes.get_mut(0..size) {
+            Some(bytes) => bytes,
+            None => return 0,
+        };
+        let (header, records) = bytes.split_at_mut(HIGH_SCORES_HEADER_SIZE);
+        header.copy_from_slice(&[HIGH_SCORES_MAGIC[0], HIGH_SCORES_MAGIC[1], HIGH_SCORES_VERSION, self.count as u8]);
+        for (record, entry) in records.chunks_exact_mut(HIGH_SCORE_RECORD_SIZE).zip(self.entries.iter()) {
+            let (name, score) = record.split_at_mut(HIGH_SCORE_NAME_LENGTH);
+            name.copy_from_slice(&entry.name);
+            score.copy_from_slice(&entry.score.to_le_bytes());
+        }
+        size
+    }
+
+    fn load(bytes: &[u8]) -> Option<Self> {
+        let (count, records) = match bytes {
+            [m0, m1, version, count, records @ ..]
+                if [*m0, *m1] == HIGH_SCORES_MAGIC && *version == HIGH_SCORES_VERSION => (*count as usize, records),
+            _ => return None,
+        };
+        if count > HIGH_SCORES_CAPACITY || records.len() < count * HIGH_SCORE_RECORD_SIZE {
+            return None;
+        }
+
+        let mut high_scores = Self::empty();
+        for record in records.chunks_exact(HIGH_SCORE_RECORD_SIZE).take(count) {
+            match record {
+                [n0, n1, n2, s0, s1, s2, s3] if [n0, n1, n2].iter().all(|c| c.is_ascii_uppercase()) => {
+                    high_scores.insert(HighScore {
+                        name: [*n0, *n1, *n2],
+                        score: u32::from_le_bytes([*s0, *s1, *s2, *s3]),
+                    });
+                }
+                _ => return None,
+            }
+        }
+        Some(high_scores)
+    }
+
+    fn render(&self, display: &mut Display, font: &Font, highlight: Option<usize>) {
+        if self.count == 0 {
+            render_centered(display, font, b"No scores yet", HIGH_SCORES_Y, HIGH_SCORES_SCALE, SCORE_LABEL_COLOR);
+        }
+        let x = (DISPLAY_WIDTH as i32 - FONT_CHAR_WIDTH as i32 * HIGH_SCORES_SCALE * HIGH_SCORES_ROW_LENGTH as i32) / 2;
+        for (rank, entry) in self.entries.iter().take(self.count).enumerate() {
+            let mut row = Label::new();
+            let _ = write!(row, "{:>2}. ", rank + 1);
+            row.push_bytes(&entry.name);
+            row.push_bytes(b"  ");
+            row.push_grouped(entry.score as usize);
+            let color = if highlight == Some(rank) {
+                MENU_SELECTED_COLOR
+            } else {
+                SCORE_LABEL_COLOR
+            };
+            row.render(display, font, x, HIGH_SCORES_Y + rank as i32 * HIGH_SCORES_ROW_HEIGHT, HIGH_SCORES_SCALE, color);
+        }
+    }
+}
+
+// Arcade style entry of the name for the high score table
+struct NameEntry {
+    name: [u8; HIGH_SCORE_NAME_LENGTH],
+    cursor: usize,
+}
+
+impl NameEntry {
+    const fn new() -> Self {
+        Self {
+            name: [b'A'; HIGH_SCORE_NAME_LENGTH],
+            cursor: 0,
+        }
+    }
+
+    fn letter_x(index: usize) -> i32 {
+        let width = NAME_ENTRY_SPACING * (HIGH_SCORE_NAME_LENGTH as i32 - 1) + FONT_CHAR_WIDTH as i32 * NAME_ENTRY_SCALE;
+        (DISPLAY_WIDTH as i32 - width) / 2 + index as i32 * NAME_ENTRY_SPACING
+    }
+
+    fn change_letter(&mut self, index: usize, delta: u8) {
+        if let Some(letter) = self.name.get_mut(index) {
+            *letter = (*letter - b'A' + delta) % 26 + b'A';
+        }
+    }
+
+    // Up and Down change the letter under the cursor, Left and Right
+    // move the cursor, clicking a letter changes it. Returns true when
+    // the name is confirmed.
+    fn update(&mut self, input: &Input) -> bool {
+        if input.pressed(Button::Up) {
+            self.change_letter(self.cursor, 1);
+        }
+        if input.pressed(Button::Down) {
+            self.change_letter(self.cursor, 25);
+        }
+        if input.pressed(Button::Left) || input.pressed(Button::Back) {
+            self.cursor = self.cursor.saturating_sub(1);
+        }
+        if input.pressed(Button::Right) {
+            self.cursor = min(self.cursor as i32 + 1, HIGH_SCORE_NAME_LENGTH as i32 - 1) as usize;
+        }
+
+        if input.mouse_pressed {
+            for index in 0..HIGH_SCORE_NAME_LENGTH {
+                let x = Self::letter_x(index);
+                if x <= input.mouse_x && input.mouse_x < x + FONT_CHAR_WIDTH as i32 * NAME_ENTRY_SCALE
+                    && NAME_ENTRY_Y <= input.mouse_y && input.mouse_y < NAME_ENTRY_Y + FONT_CHAR_HEIGHT as i32 * NAME_ENTRY_SCALE {
+                    self.cursor = index;
+                    self.change_letter(index, 1);
+                }
+            }
+        }
+
+        if input.pressed(Button::Confirm) {
+            if self.cursor + 1 < HIGH_SCORE_NAME_LENGTH {
+                self.cursor += 1;
+            } else {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn render(&self, display: &mut Display, font: &Font, score: &Label, time: Seconds) {
+        let x = (DISPLAY_WIDTH as i32 - score.width(font, NAME_ENTRY_SCORE_SCALE)) / 2;
+        score.render(display, font, x, NAME_ENTRY_SCORE_Y, NAME_ENTRY_SCORE_SCALE, SCORE_LABEL_COLOR);
+
+        for (index, letter) in self.name.iter().enumerate() {
+            let x = Self::letter_x(index);
+            let color = if index == self.cursor {
+                MENU_SELECTED_COLOR
+            } else {
+                SCORE_LABEL_COLOR
+            };
+            let mut text = Label::new();
+            text.push_byte(*letter);
+            text.render(display, font, x, NAME_ENTRY_Y, NAME_ENTRY_SCALE, color);
+            if index == self.cursor && (time / PROMPT_BLINK_PERIOD) as i32 % 2 == 0 {
+                display.fill_rect(x, NAME_ENTRY_Y + FONT_CHAR_HEIGHT as i32 * NAME_ENTRY_SCALE + NAME_ENTRY_SCALE,
+                                  FONT_CHAR_WIDTH as i32 * NAME_ENTRY_SCALE, NAME_ENTRY_SCALE, color);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scene {
+    Title,
+    MainMenu,
+    Playing,
+    Paused,
+    GameOver,
+    Settings,
+    HighScores,
+    NameEntry,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MenuItem {
+    Play,
+    Resume,
+    Settings,
+    HighScores,
+    MainMenu,
+    Back,
+    Done,
+}
+
+impl MenuItem {
+    const fn label(self) -> &'static [u8] {
+        match self {
+            Self::Play => b"Play",
+            Self::Resume => b"Resume",
+            Self::Settings => b"Settings",
+            Self::HighScores => b"High Scores",
+            Self::MainMenu => b"Main Menu",
+            Self::Back => b"Back",
+            Self::Done => b"Done",
+        }
+    }
+}
+
+impl Scene {
+    const fn heading(self) -> &'static [u8] {
+        match self {
+            Self::Title | Self::Playing => b"",
+            Self::MainMenu => TITLE_TEXT,
+            Self::Paused => b"Paused",
+            Self::GameOver => b"Game Over",
+            Self::Settings => b"Settings",
+            Self::HighScores => b"High Scores",
+            Self::NameEntry => b"New High Score",
+        }
+    }
+
+    const fn menu(self) -> &'static [MenuItem] {
+        match self {
+            Self::Title | Self::Playing => &[],
+            Self::MainMenu => &[MenuItem::Play, MenuItem::Settings, MenuItem::HighScores],
+            Self::Paused => &[MenuItem::Resume, MenuItem::Settings, MenuItem::MainMenu],
+            Self::GameOver => &[MenuItem::Play, MenuItem::HighScores, MenuItem::MainMenu],
+            Self::Settings | Self::HighScores => &[MenuItem::Back],
+            Self::NameEntry => &[MenuItem::Done],
+        }
+    }
+
+    const fn menu_y(self) -> i32 {
+        match self {
+            Self::Settings => SETTINGS_MENU_Y,
+            Self::HighScores => HIGH_SCORES_MENU_Y,
+            Self::NameEntry => NAME_ENTRY_MENU_Y,
+            _ => MENU_Y,
+        }
+    }
+
+    // The game stays visible under the menu of these scenes
+    const fn over_game(self) -> bool {
+        matches!(self, Self::Playing | Self::Paused | Self::GameOver | Self::NameEntry)
+    }
+}
+
+// Abstract buttons, so the frontends can map keyboards and gamepads
+// onto them as they like
+#[derive(Clone, Copy)]
+#[repr(u32)]
+enum Button {
+    Up = 0,
+    Down = 1,
+    Left = 2,
+    Right = 3,
+    Confirm = 4,
+    Back = 5,
+    Pause = 6,
+}
+const BUTTONS_COUNT: u32 = 7;
+
+// Input collected between the frames. The `pressed` flags only last
+// for the frame the button went down in.
+struct Input {
+    mouse_x: i32,
+    mouse_y: i32,
+    mouse_moved: bool,
+    mouse_held: bool,
+    mouse_pressed: bool,
+    buttons_held: u32,
+    buttons_pressed: u32,
+}
+
+impl Input {
+    const fn new() -> Self {
+        Self {
+            mouse_x: 0,
+            mouse_y: 0,
+            mouse_moved: false,
+            mouse_held: false,
+            mouse_pressed: false,
+            buttons_held: 0,
+            buttons_pressed: 0,
+        }
+    }
+
+    fn held(&self, button: Button) -> bool {
+        self.buttons_held & (1 << button as u32) != 0
+    }
+
+    fn pressed(&self, button: Button) -> bool {
+        self.buttons_pressed & (1 << button as u32) != 0
+    }
+
+    fn button_down(&mut self, button: u32) {
+        if button < BUTTONS_COUNT {
+            self.buttons_held |= 1 << button;
+            self.buttons_pressed |= 1 << button;
+        }
+    }
+
+    fn button_up(&mut self, button: u32) {
+        if button < BUTTONS_COUNT {
+            self.buttons_held &= !(1 << button);
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.mouse_moved = false;
+        self.mouse_pressed = false;
+        self.buttons_pressed = 0;
+    }
+}
+
+fn render_centered(display: &mut Display, font: &Font, text: &[u8], y: i32, scale: i32, color: Pixel) {
+    let rect = Rect::new(0, y, DISPLAY_WIDTH as i32, DISPLAY_HEIGHT as i32 - y);
+    font.render_text(display, text, rect, scale, Align::Center, color);
+}
+
+// Immediate-mode UI. Widgets are laid out top to bottom in a column
+// centered on the display, handle the input and draw themselves in a
+// single call, and report whether the user changed them. Only the
+// focused widget index has to be kept between the frames.
+struct Ui<'a> {
+    display: &'a mut Display,
+    font: &'a Font,
+    input: &'a Input,
+    focus: usize,
+    count: usize,
+    y: i32,
+}
+
+// Where the widget is and how the user interacts with it this frame
+struct Interaction {
+    x: i32,
+    y: i32,
+    hovered: bool,
+    focused: bool,
+    activated: bool,
+}
+
+impl<'a> Ui<'a> {
+    fn new(display: &'a mut Display, font: &'a Font, input: &'a Input, focus: usize, y: i32) -> Self {
+        Self {
+            display,
+            font,
+            input,
+            focus,
+            count: 0,
+            y,
+        }
+    }
+
+    fn text_width(&self, text: &Label) -> i32 {
+        text.width(self.font, MENU_SCALE)
+    }
+
+    // Takes the next row of the column for a widget of the width
+    fn widget(&mut self, width: i32) -> Interaction {
+        let index = self.count;
+        let x = (DISPLAY_WIDTH as i32 - width) / 2;
+        let y = self.y;
+        self.count += 1;
+        self.y += MENU_ITEM_HEIGHT;
+
+        let input = self.input;
+        let hovered = x <= input.mouse_x && input.mouse_x < x + width
+            && y <= input.mouse_y && input.mouse_y < y + FONT_CHAR_HEIGHT as i32 * MENU_SCALE;
+        // The mouse only takes the focus when it moves, so it does
+        // not fight with the keyboard
+        if hovered && input.mouse_moved {
+            self.focus = index;
+        }
+        let focused = self.focus == index;
+        let activated = (hovered && input.mouse_pressed) || (focused && input.pressed(Button::Confirm));
+
+        if hovered {
+            self.display.blend_rect(x - UI_HOVER_PADDING, y - UI_HOVER_PADDING,
+                                    width + UI_HOVER_PADDING * 2,
+                                    FONT_CHAR_HEIGHT as i32 * MENU_SCALE + UI_HOVER_PADDING * 2,
+                                    UI_HOVER_COLOR);
+        }
+        Interaction { x, y, hovered, focused, activated }
+    }
+
+    fn color(interaction: &Interaction) -> Pixel {
+        if interaction.focused {
+            MENU_SELECTED_COLOR
+        } else {
+            SCORE_LABEL_COLOR
+        }
+    }
+
+    // Returns true when clicked or confirmed
+    fn button(&mut self, label: &[u8]) -> bool {
+        let mut text = Label::new();
+        text.push_bytes(label);
+        let interaction = self.widget(self.text_width(&text));
+        text.render(self.display, self.font, interaction.x, interaction.y, MENU_SCALE, Self::color(&interaction));
+        interaction.activated
+    }
+
+    // Returns true when the value was flipped
+    fn toggle(&mut self, label: &[u8], value: &mut bool) -> bool {
+        let mut text = Label::new();
+        text.push_bytes(label);
+        text.push_bytes(if *value { b": On" } else { b": Off" });
+        // Reserve the space for the longest value so the widget does
+        // not jump around when toggled
+        let interaction = self.widget(self.text_width(&text) + if *value { FONT_CHAR_WIDTH as i32 * MENU_SCALE } else { 0 });
+        let changed = interaction.activated
+            || (interaction.focused && (self.input.pressed(Button::Left) || self.input.pressed(Button::Right)));
+        if changed {
+            *value = !*value;
+        }
+        text.render(self.display, self.font, interaction.x, interaction.y, MENU_SCALE, Self::color(&interaction));
+        changed
+    }
+
+    // Returns true when the value was moved. Can be dragged with the
+    // mouse or stepped with Left and Right.
+    fn slider(&mut self, label: &[u8], value: &mut i32, low: i32, high: i32, step: i32) -> bool {
+        let step = max(step, 1);
+        let mut text = Label::new();
+        text.push_bytes(label);
+        let text_width = self.text_width(&text);
+        let interaction = self.widget(text_width + UI_SLIDER_GAP + UI_SLIDER_WIDTH);
+        let bar_x = interaction.x + text_width + UI_SLIDER_GAP;
+
+        let old = *value;
+        if interaction.focused && self.input.pressed(Button::Left) {
+            *value -= step;
+        }
+        if interaction.focused && self.input.pressed(Button::Right) {
+            *value += step;
+        }
+        if interaction.hovered && self.input.mouse_held && self.input.mouse_x >= bar_x && high > low {
+            let offset = self.input.mouse_x - bar_x;
+            *value = low + (offset * (high - low) + UI_SLIDER_WIDTH / 2) / UI_SLIDER_WIDTH / step * step;
+        }
+        *value = clamp(*value, low, high);
+
+        let color = Self::color(&interaction);
+        text.render(self.display, self.font, interaction.x, interaction.y, MENU_SCALE, color);
+        let bar_y = interaction.y + (FONT_CHAR_HEIGHT as i32 * MENU_SCALE - UI_SLIDER_HEIGHT) / 2;
+        let filled = if high > low {
+            UI_SLIDER_WIDTH * (*value - low) / (high - low)
+        } else {
+            0
+        };
+        self.display.fill_rect(bar_x - SHADOW_OFFSET, bar_y - SHADOW_OFFSET, UI_SLIDER_WIDTH, UI_SLIDER_HEIGHT, SHADOW_COLOR);
+        self.display.fill_rect(bar_x, bar_y, UI_SLIDER_WIDTH, UI_SLIDER_HEIGHT, BOSS_BAR_BACKGROUND);
+        self.display.fill_rect(bar_x, bar_y, filled, UI_SLIDER_HEIGHT, color);
+        *value != old
+    }
+
+    // Picks one of the options. Returns true when the selection changed.
+    fn list(&mut self, label: &[u8], options: &[&[u8]], selected: &mut usize) -> bool {
+        if options.is_empty() {
+            return false;
         }
+        let mut text = Label::new();
+        text.push_bytes(label);
+        text.push_bytes(b": < ");
+        text.push_bytes(options.get(*selected).cloned().unwrap_or(b"?"));
+        text.push_bytes(b" >");
+        let interaction = self.widget(self.text_width(&text));
+
+        let old = *selected;
+        if interaction.activated || (interaction.focused && self.input.pressed(Button::Right)) {
+            *selected = (*selected + 1) % options.len();
+        }
+        if interaction.focused && self.input.pressed(Button::Left) {
+            *selected = (*selected + options.len() - 1) % options.len();
+        }
+        text.render(self.display, self.font, interaction.x, interaction.y, MENU_SCALE, Self::color(&interaction));
+        *selected != old
+    }
+
+    // Moves the focus with Up and Down. Returns the focus to keep until
+    // the next frame.
+    fn end(self) -> usize {
+        if self.count == 0 {
+            return 0;
+        }
+        let mut focus = self.focus.min(self.count - 1);
+        if self.input.pressed(Button::Up) {
+            focus = (focus + self.count - 1) % self.count;
+        }
+        if self.input.pressed(Button::Down) {
+            focus = (focus + 1) % self.count;
+        }
+        focus
     }
 }
 
 #[repr(C)]
 pub struct State {
-    player: Entity,
-    bullets: [Entity; BULLETS_CAPACITY],
-    enemies: [Entity; ENEMIES_CAPACITY],
-    enemy_spawn_cooldown: Seconds,
-    pause: bool,
-    score: usize,
-    score_label: Label,
-    rng: Rng,
+    scene: Scene,
+    // Where the Back item of Settings and High Scores returns to
+    previous_scene: Scene,
+    // Focused widget of the scene's menu
+    focus: usize,
+    time: Seconds,
+    input: Input,
+    game: Game,
+    seed: u64,
+    audio: audio::Audio,
+    config: Config,
+    config_changed: bool,
+    blob: [u8; BLOB_CAPACITY],
+    fps: f32,
+    fps_label: Label,
+    high_scores: HighScores,
+    high_scores_changed: bool,
+    // Rank of the score entered in this session to highlight it
+    new_high_score: Option<usize>,
+    name_entry: NameEntry,
+    popups: Popups,
 }
 
 impl State {
     const fn default() -> Self {
         Self {
-            player: Entity::new(0, DISPLAY_HEIGHT as i32 - PLAYER_SIZE),
-            bullets: [Entity::dead(); BULLETS_CAPACITY],
-            enemies: [Entity::dead(); ENEMIES_CAPACITY],
-            enemy_spawn_cooldown: 0.0,
-            pause: false,
-            score: 0,
-            score_label: Label::empty(),
-            rng: Rng::from_seed(123456789),
+            scene: Scene::Title,
+            previous_scene: Scene::Title,
+            focus: 0,
+            time: 0.0,
+            input: Input::new(),
+            game: Game::default(),
+            seed: RNG_DEFAULT_SEED,
+            audio: audio::Audio::new(),
+            config: Config::default(),
+            config_changed: false,
+            blob: [0; BLOB_CAPACITY],
+            fps: 0.0,
+            fps_label: Label::new(),
+            high_scores: HighScores::empty(),
+            high_scores_changed: false,
+            new_high_score: None,
+            name_entry: NameEntry::new(),
+            popups: Popups::new(RNG_DEFAULT_SEED),
         }
     }
 
     fn update(&mut self, dt: Seconds) {
-        if !self.pause {
-            for bullet in self.bullets.iter_mut() {
-                if bullet.alive {
-                    bullet.y -= (BULLET_SPEED as f32 * dt) as i32;
-                    if bullet.y - BULLET_SIZE / 2 < 0 {
-                        bullet.alive = false
-                    }
+        self.time += dt;
+        if dt > 0.0 {
+            self.fps += (1.0 / dt - self.fps) * FPS_SMOOTHING;
+        }
+        self.fps_label.clear();
+        let _ = write!(self.fps_label, "FPS: {:.1}", self.fps);
+
+        // The popups freeze together with the game on pause
+        if self.scene != Scene::Paused {
+            self.popups.update(dt);
+        }
+
+        match self.scene {
+            Scene::Title => {
+                if self.input.mouse_pressed || self.input.buttons_pressed != 0 {
+                    self.switch_scene(Scene::MainMenu);
                 }
             }
-
-            for enemy in self.enemies.iter_mut() {
-                if enemy.alive {
-                    // Update Enemy's position and despawn it if it
-                    // went outside of the screen
-                    {
-                        enemy.y += (ENEMY_SPEED as f32 * dt) as i32;
-                        if enemy.y - ENEMY_SIZE / 2 > DISPLAY_HEIGHT as i32 {
-                            enemy.alive = false
+            Scene::Playing => {
+                if self.input.pressed(Button::Pause) || self.input.pressed(Button::Back) {
+                    self.switch_scene(Scene::Paused);
+                } else {
+                    let (trigger, tap) = match self.config.controls {
+                        Controls::Mouse => {
+                            if self.input.mouse_moved {
+                                self.game.move_player(self.input.mouse_x);
+                            }
+                            (self.input.mouse_held, self.input.mouse_pressed)
                         }
-                    }
-
-                    // Check if any bullets killed the enemy by
-                    // overlaping with it
-                    for bullet in self.bullets.iter_mut() {
-                        if bullet.alive {
-                            if enemy.overlaps(ENEMY_SIZE, bullet, BULLET_SIZE) {
-                                enemy.alive = false;
-                                bullet.alive = false;
-                                self.score += PLAYER_KILL_REWARD;
-                                break;
+                        Controls::Buttons => {
+                            let mut x = self.game.player.x;
+                            if self.input.held(Button::Left) {
+                                x -= (PLAYER_BUTTONS_SPEED as f32 * dt) as i32;
                             }
+                            if self.input.held(Button::Right) {
+                                x += (PLAYER_BUTTONS_SPEED as f32 * dt) as i32;
+                            }
+                            self.game.move_player(clamp(x, 0, DISPLAY_WIDTH as i32));
+                            (self.input.held(Button::Confirm), self.input.pressed(Button::Confirm))
                         }
-                    }
-                }
+                    };
 
-                // Enemy could be killed by a bullet in the previous
-                // condition. So we need to check if it's alive again.
-                if enemy.alive {
-                    // Check if any enemy killed the player by
-                    // overlaping with them.
-                    if enemy.overlaps(ENEMY_SIZE, &self.player, PLAYER_SIZE) {
-                        self.player.alive = false;
+                    self.game.trigger = trigger;
+                    // Shoot right away so a quick tap that is released
+                    // before the next frame still fires
+                    if tap {
+                        self.game.shoot(&mut self.audio);
+                    }
+                    self.game.update(dt, &mut self.audio, &mut self.popups);
+                    if !self.game.player.alive {
+                        info!("Game over with the score {}", self.game.score);
+                        if self.high_scores.qualifies(self.game.score as u32) {
+                            self.name_entry.cursor = 0;
+                            self.switch_scene(Scene::NameEntry);
+                        } else {
+                            self.switch_scene(Scene::GameOver);
+                        }
                     }
                 }
             }
+            Scene::GameOver => {
+                // The world keeps going without the player
+                self.game.update(dt, &mut self.audio, &mut self.popups);
+                self.go_back();
+            }
+            Scene::NameEntry => {
+                self.game.update(dt, &mut self.audio, &mut self.popups);
+                if self.name_entry.update(&self.input) {
+                    self.enter_high_score();
+                }
+            }
+            Scene::Paused if self.input.pressed(Button::Pause) => self.switch_scene(Scene::Playing),
+            Scene::MainMenu | Scene::Paused | Scene::Settings | Scene::HighScores => self.go_back(),
+        }
+
+        let boss = self.scene.over_game() && self.game.boss().is_some();
+        self.audio.set_music_intensity(if !self.scene.over_game() {
+            MUSIC_MENU_INTENSITY
+        } else if boss {
+            MUSIC_BOSS_INTENSITY
+        } else {
+            MUSIC_INTENSITY
+        });
+        self.audio.set_music_tempo(if self.scene.over_game() && self.game.lives == 1 {
+            MUSIC_LOW_HEALTH_TEMPO
+        } else if boss {
+            MUSIC_BOSS_TEMPO
+        } else {
+            100
+        });
+
+        // The sound keeps playing during the pause so the frontend
+        // never runs out of samples
+        self.audio.render(dt);
+    }
 
-            self.enemy_spawn_cooldown -= dt;
-            if self.enemy_spawn_cooldown < 0.0 {
-                let enemy_x = self.rng.rand().abs() % DISPLAY_WIDTH as i32;
-                self.spawn_enemy(enemy_x, -ENEMY_SIZE / 2);
-                self.enemy_spawn_cooldown = ENEMY_SPAWN_PERIOD;
+    fn go_back(&mut self) {
+        if self.input.pressed(Button::Back) {
+            match self.scene {
+                Scene::MainMenu => self.switch_scene(Scene::Title),
+                Scene::Paused => self.switch_scene(Scene::Playing),
+                Scene::GameOver => self.switch_scene(Scene::MainMenu),
+                Scene::Settings | Scene::HighScores => self.switch_scene(self.previous_scene),
+                Scene::Title | Scene::Playing | Scene::NameEntry => {}
             }
+        }
+    }
 
-            self.score_label.clear();
-            self.score_label.push_bytes(b"Score: ");
-            self.score_label.push_usize(self.score);
+    fn activate(&mut self, item: MenuItem) {
+        match item {
+            MenuItem::Play => self.new_game(),
+            MenuItem::Resume => self.switch_scene(Scene::Playing),
+            MenuItem::Settings => {
+                self.previous_scene = self.scene;
+                self.switch_scene(Scene::Settings);
+            }
+            MenuItem::HighScores => {
+                self.previous_scene = self.scene;
+                self.switch_scene(Scene::HighScores);
+            }
+            MenuItem::MainMenu => self.switch_scene(Scene::MainMenu),
+            MenuItem::Back => self.switch_scene(self.previous_scene),
+            MenuItem::Done => self.enter_high_score(),
         }
     }
 
-    fn render(&self, display: &mut Display, font: &Font) {
-        if !self.pause {
+    fn enter_high_score(&mut self) {
+        self.new_high_score = self.high_scores.insert(HighScore {
+            name: self.name_entry.name,
+            score: self.game.score as u32,
+        });
+        self.high_scores_changed = true;
+        self.previous_scene = Scene::GameOver;
+        self.switch_scene(Scene::HighScores);
+    }
+
+    fn switch_scene(&mut self, scene: Scene) {
+        self.scene = scene;
+        self.focus = 0;
+        self.game.trigger = false;
+        // The input that caused the switch must not be handled again
+        // by the new scene
+        self.input.end_frame();
+    }
+
+    fn new_game(&mut self) {
+        info!("New game with the seed {}", self.seed);
+        self.game = Game::default();
+        self.game.set_seed(self.seed);
+        self.popups = Popups::new(self.seed);
+        self.game.difficulty = self.config.difficulty;
+        self.game.lives = self.config.difficulty.lives();
+        self.switch_scene(Scene::Playing);
+    }
+
+    // Menus are immediate-mode UI, so they handle the input while
+    // being rendered
+    fn render(&mut self, display: &mut Display, font: &Font) {
+        if self.scene.over_game() {
+            self.game.render(display, font);
+            if self.scene != Scene::Playing {
+                display.blend_rect(0, 0, DISPLAY_WIDTH as i32, DISPLAY_HEIGHT as i32, OVERLAY_COLOR);
+            }
+        } else {
             display.fill(DISPLAY_BACKGROUND);
-            self.player.render(display, PLAYER_SIZE, PLAYER_COLOR);
-            for bullet in self.bullets.iter() {
-                bullet.render(display, BULLET_SIZE, BULLET_COLOR)
+        }
+
+        match self.scene {
+            Scene::Title => {
+                render_centered(display, font, TITLE_TEXT, TITLE_Y, TITLE_SCALE, SCORE_LABEL_COLOR);
+                if (self.time / PROMPT_BLINK_PERIOD) as i32 % 2 == 0 {
+                    render_centered(display, font, PROMPT_TEXT, PROMPT_Y, PROMPT_SCALE, SCORE_LABEL_COLOR);
+                }
             }
-            for enemy in self.enemies.iter() {
-                enemy.render(display, ENEMY_SIZE, ENEMY_COLOR)
+            _ => render_centered(display, font, self.scene.heading(), HEADING_Y, HEADING_SCALE, SCORE_LABEL_COLOR),
+        }
+
+        match self.scene {
+            Scene::HighScores => self.high_scores.render(display, font, self.new_high_score),
+            Scene::NameEntry => self.name_entry.render(display, font, &self.game.score_label, self.time),
+            _ => {}
+        }
+
+        if self.config.show_fps {
+            let x = DISPLAY_WIDTH as i32 - LIFE_ICON_PADDING - self.fps_label.width(font, FPS_LABEL_SCALE);
+            self.fps_label.render(display, font, x, FPS_LABEL_Y, FPS_LABEL_SCALE, SCORE_LABEL_COLOR);
+        }
+
+        let mut activated = None;
+        {
+            // The letters own the keyboard while the name is entered,
+            // so Done is only focused by the mouse
+            let focus = if self.scene == Scene::NameEntry { usize::MAX } else { self.focus };
+            let mut ui = Ui::new(display, font, &self.input, focus, self.scene.menu_y());
+            if self.scene == Scene::Settings {
+                let config = &mut self.config;
+                let mut changed = ui.slider(b"Volume", &mut config.volume, 0, 100, VOLUME_STEP);
+
+                let mut difficulty = config.difficulty as usize;
+                if ui.list(b"Difficulty", &DIFFICULTY_NAMES, &mut difficulty) {
+                    config.difficulty = Difficulty::from_index(difficulty).unwrap_or(config.difficulty);
+                    changed = true;
+                }
+
+                let mut controls = config.controls as usize;
+                if ui.list(b"Controls", &CONTROLS_NAMES, &mut controls) {
+                    config.controls = Controls::from_index(controls).unwrap_or(config.controls);
+                    changed = true;
+                }
+
+                let mut palette = config.palette as usize;
+                if ui.list(b"Palette", &PALETTE_NAMES, &mut palette) {
+                    config.palette = Palette::from_index(palette).unwrap_or(config.palette);
+                    changed = true;
+                }
+
+                changed |= ui.toggle(b"Show FPS", &mut config.show_fps);
+                if changed {
+                    self.audio.set_volume(config.volume as u32);
+                    self.config_changed = true;
+                }
             }
-            self.score_label.render(display, font,
-                                    SCORE_LABEL_X,
-                                    SCORE_LABEL_Y,
-                                    4,
-                                    SCORE_LABEL_COLOR);
-            {
-                let x = (DISPLAY_WIDTH  - FONT_CHAR_WIDTH * COPYRIGHT_SCALE as usize * COPYRIGHT_TEXT.len() - COPYRIGHT_PADDING) as i32;
-                let y = (DISPLAY_HEIGHT - FONT_CHAR_HEIGHT * COPYRIGHT_SCALE as usize - COPYRIGHT_PADDING) as i32;
-                font.render_bytes(
-                    display,
-                    COPYRIGHT_TEXT,
-                    x - SHADOW_OFFSET,
-                    y - SHADOW_OFFSET,
-                    COPYRIGHT_SCALE,
-                    SHADOW_COLOR);
-                font.render_bytes(
-                    display,
-                    COPYRIGHT_TEXT,
-                    x,
-                    y,
-                    COPYRIGHT_SCALE,
-                    SCORE_LABEL_COLOR);
-            }
-        }
-    }
-
-    fn spawn_enemy(&mut self, x: i32, y: i32) {
-        for enemy in self.enemies.iter_mut() {
-            if !enemy.alive {
-                enemy.revive(x, y);
-                break;
+            for item in self.scene.menu() {
+                if ui.button(item.label()) {
+                    activated = Some(*item);
+                }
             }
+            self.focus = ui.end();
+        }
+        if let Some(item) = activated {
+            self.activate(item);
         }
+
+        self.popups.render(display, font);
+
+        let (_, height) = font.measure(COPYRIGHT_TEXT, COPYRIGHT_SCALE, TEXT_NO_WRAP);
+        let rect = Rect::new(COPYRIGHT_PADDING,
+                             DISPLAY_HEIGHT as i32 - COPYRIGHT_PADDING - height,
+                             DISPLAY_WIDTH as i32 - COPYRIGHT_PADDING * 2,
+                             height);
+        font.render_text(display, COPYRIGHT_TEXT, rect, COPYRIGHT_SCALE, Align::Right, SCORE_LABEL_COLOR);
+
+        self.config.palette.apply(display);
+        self.input.end_frame();
     }
 
-    fn spawn_bullet(&mut self, x: i32, y: i32) {
-        for bullet in self.bullets.iter_mut() {
-            if !bullet.alive {
-                bullet.revive(x, y);
-                break;
-            }
+    fn mouse_move(&mut self, x: i32, y: i32) {
+        self.input.mouse_x = x;
+        self.input.mouse_y = y;
+        self.input.mouse_moved = true;
+    }
+
+    // A press and release within the same frame
+    fn mouse_click(&mut self) {
+        self.input.mouse_pressed = true;
+    }
+
+    fn mouse_down(&mut self) {
+        self.input.mouse_held = true;
+        self.input.mouse_pressed = true;
+    }
+
+    fn mouse_up(&mut self) {
+        self.input.mouse_held = false;
+    }
+
+    fn key_down(&mut self, button: u32) {
+        self.input.button_down(button);
+    }
+
+    fn key_up(&mut self, button: u32) {
+        self.input.button_up(button);
+    }
+
+    fn select_weapon(&mut self, index: usize) {
+        if self.scene == Scene::Playing {
+            self.game.select_weapon(index);
         }
     }
 
-    fn mouse_move(&mut self, x: i32, _y: i32) {
-        if self.player.alive {
-            self.player.x = x;
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    fn save_config(&mut self) -> usize {
+        self.config.save(&mut self.blob)
+    }
+
+    fn load_config(&mut self, size: usize) -> bool {
+        match self.blob.get(0..size).and_then(Config::load) {
+            Some(config) => {
+                self.config = config;
+                self.audio.set_volume(config.volume as u32);
+                true
+            }
+            None => {
+                warn!("Ignoring the unsupported config");
+                false
+            }
         }
     }
 
-    fn mouse_click(&mut self) {
-        if self.player.alive {
-            self.spawn_bullet(
-                self.player.x,
-                self.player.y - PLAYER_SIZE / 2 - BULLET_SIZE / 2,
-            );
+    // Tells the host when to save the config
+    fn poll_config_changed(&mut self) -> bool {
+        let changed = self.config_changed;
+        self.config_changed = false;
+        changed
+    }
+
+    fn save_high_scores(&mut self) -> usize {
+        self.high_scores.save(&mut self.blob)
+    }
+
+    fn load_high_scores(&mut self, size: usize) -> bool {
+        match self.blob.get(0..size).and_then(HighScores::load) {
+            Some(high_scores) => {
+                self.high_scores = high_scores;
+                true
+            }
+            None => {
+                warn!("Ignoring the unsupported high scores");
+                false
+            }
         }
     }
 
+    fn poll_high_scores_changed(&mut self) -> bool {
+        let changed = self.high_scores_changed;
+        self.high_scores_changed = false;
+        changed
+    }
+
     fn toggle_pause(&mut self) {
-        self.pause = !self.pause
+        match self.scene {
+            Scene::Playing => self.switch_scene(Scene::Paused),
+            Scene::Paused => self.switch_scene(Scene::Playing),
+            _ => {}
+        }
     }
 }
 
@@ -570,6 +3780,32 @@ pub unsafe extern "C" fn get_display() -> &'static mut Display {
     &mut DISPLAY
 }
 
+/// # Safety
+/// Must be called from the single wasm thread. The ring is rewritten
+/// by every `next_frame`, so the host has to copy the samples out
+/// before the next one.
+#[no_mangle]
+pub unsafe extern "C" fn get_audio_buffer() -> *const i16 {
+    STATE.audio.ring().as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn get_audio_buffer_len() -> usize {
+    audio::RING_CAPACITY
+}
+
+#[no_mangle]
+pub extern "C" fn get_audio_sample_rate() -> u32 {
+    audio::SAMPLE_RATE
+}
+
+/// # Safety
+/// Must be called from the single wasm thread between the frames.
+#[no_mangle]
+pub unsafe extern "C" fn get_audio_position() -> usize {
+    STATE.audio.position()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn next_frame(dt: Seconds) {
     STATE.update(dt);
@@ -581,24 +3817,163 @@ pub unsafe extern "C" fn mouse_move(x: i32, y: i32) {
     STATE.mouse_move(x, y);
 }
 
+// Kept for the frontends that only report the clicks
 #[no_mangle]
 pub unsafe extern "C" fn mouse_click() {
     STATE.mouse_click();
 }
 
+/// # Safety
+/// Must be called from the single wasm thread between the frames.
 #[no_mangle]
-pub unsafe extern "C" fn toggle_pause() {
-    STATE.toggle_pause();
+pub unsafe extern "C" fn mouse_down() {
+    STATE.mouse_down();
 }
 
-#[allow(dead_code)]
-extern "C" {
-    fn js_sin(x: f32) -> f32;
-    fn js_cos(x: f32) -> f32;
+/// # Safety
+/// Must be called from the single wasm thread between the frames.
+#[no_mangle]
+pub unsafe extern "C" fn mouse_up() {
+    STATE.mouse_up();
+}
+
+/// # Safety
+/// Must be called from the single wasm thread between the frames.
+/// Unknown buttons are ignored.
+#[no_mangle]
+pub unsafe extern "C" fn key_down(button: u32) {
+    STATE.key_down(button);
+}
+
+/// # Safety
+/// Must be called from the single wasm thread between the frames.
+/// Unknown buttons are ignored.
+#[no_mangle]
+pub unsafe extern "C" fn key_up(button: u32) {
+    STATE.key_up(button);
+}
+
+/// # Safety
+/// Must be called from the single wasm thread between the frames.
+/// Out of range indices are ignored.
+#[no_mangle]
+pub unsafe extern "C" fn select_weapon(index: usize) {
+    STATE.select_weapon(index);
+}
+
+/// # Safety
+/// Must be called from the single wasm thread. The seed is used by
+/// the next new game.
+#[no_mangle]
+pub unsafe extern "C" fn set_seed(seed: u32) {
+    STATE.set_seed(seed as u64);
+}
+
+/// # Safety
+/// Must be called from the single wasm thread, like every other
+/// export touching the module's globals.
+#[no_mangle]
+pub unsafe extern "C" fn set_log_level(level: u32) {
+    LOG_LEVEL = level;
+}
+
+/// # Safety
+/// Must be called from the single wasm thread. The host may only
+/// access the `get_blob_capacity()` bytes behind the pointer.
+#[no_mangle]
+pub unsafe extern "C" fn get_blob() -> *mut u8 {
+    STATE.blob.as_mut_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn get_blob_capacity() -> usize {
+    BLOB_CAPACITY
+}
+
+/// # Safety
+/// Must be called from the single wasm thread. The settings
+/// overwrite the blob.
+#[no_mangle]
+pub unsafe extern "C" fn save_config() -> usize {
+    STATE.save_config()
+}
+
+/// # Safety
+/// Must be called from the single wasm thread after the host has
+/// written `size` bytes into the blob. Sizes past the capacity are
+/// rejected.
+#[no_mangle]
+pub unsafe extern "C" fn load_config(size: usize) -> bool {
+    STATE.load_config(size)
+}
+
+/// # Safety
+/// Must be called from the single wasm thread between the frames.
+#[no_mangle]
+pub unsafe extern "C" fn poll_config_changed() -> bool {
+    STATE.poll_config_changed()
+}
+
+/// # Safety
+/// Must be called from the single wasm thread. The high scores
+/// overwrite the blob.
+#[no_mangle]
+pub unsafe extern "C" fn save_high_scores() -> usize {
+    STATE.save_high_scores()
+}
+
+/// # Safety
+/// Must be called from the single wasm thread after the host has
+/// written `size` bytes into the blob. Sizes past the capacity are
+/// rejected.
+#[no_mangle]
+pub unsafe extern "C" fn load_high_scores(size: usize) -> bool {
+    STATE.load_high_scores(size)
+}
+
+/// # Safety
+/// Must be called from the single wasm thread between the frames.
+#[no_mangle]
+pub unsafe extern "C" fn poll_high_scores_changed() -> bool {
+    STATE.poll_high_scores_changed()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toggle_pause() {
+    STATE.toggle_pause();
 }
 
 // TODO: player's health
-// TODO: game over sign
-// TODO: pause sign
 // TODO: increasing rate of spawning
 // TODO: Introduce Point and Style structs
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn popups_at_the_display_edges_do_not_wrap_around() {
+        unsafe {
+            init();
+            for &x in [0, DISPLAY_WIDTH as i32 - 1].iter() {
+                DISPLAY.fill(DISPLAY_BACKGROUND);
+                let mut popups = Popups::new(RNG_DEFAULT_SEED);
+                if let Some(label) = popups.spawn(x, DISPLAY_HEIGHT as i32 / 2, SCORE_POPUP_LIFETIME, SCORE_LABEL_COLOR) {
+                    let _ = write!(label, "+1000");
+                }
+                popups.render(&mut DISPLAY, &FONT);
+
+                let mut drawn = 0;
+                for (index, pixel) in DISPLAY.pixels.iter().enumerate() {
+                    if pixel.0 != DISPLAY_BACKGROUND.0 {
+                        let pixel_x = (index % DISPLAY_WIDTH) as i32;
+                        assert!((pixel_x - x).abs() < DISPLAY_WIDTH as i32 / 2,
+                                "the popup at {} wrapped around to {}", x, pixel_x);
+                        drawn += 1;
+                    }
+                }
+                assert!(drawn > 0, "the popup at {} was not drawn", x);
+            }
+        }
+    }
+}
@@ -1,45 +1,224 @@
 async function start() {
+    const gameCanvas = document.getElementById("game-canvas");
+    const ctx = gameCanvas.getContext('2d');
+
+    let crashed = false;
     const game = await WebAssembly.instantiateStreaming(
         fetch("game.wasm"),
         {
             "env": {
-                "js_sin": Math.sin,
-                "js_cos": Math.cos
+                "js_panic": (messagePtr, messageLen) => {
+                    const message = new TextDecoder().decode(
+                        new Uint8Array(game.instance.exports.memory.buffer, messagePtr, messageLen));
+                    console.error(message);
+                    showCrashScreen(message);
+                    crashed = true;
+                },
+                "js_log": (level, messagePtr, messageLen) => {
+                    const message = new TextDecoder().decode(
+                        new Uint8Array(game.instance.exports.memory.buffer, messagePtr, messageLen));
+                    const print = [console.error, console.warn, console.info, console.debug][level] || console.log;
+                    print(message);
+                }
             }
         });
     const memoryView = new Uint8Array(game.instance.exports.memory.buffer);
 
+    function showCrashScreen(message) {
+        ctx.fillStyle = '#3F3F3F';
+        ctx.fillRect(0, 0, gameCanvas.width, gameCanvas.height);
+        ctx.fillStyle = '#DCDCCC';
+        ctx.font = '20px monospace';
+        const lines = ['The game has crashed :(', ''].concat(message.split('\n'));
+        lines.forEach((line, i) => ctx.fillText(line, 20, 40 + i * 28));
+    }
+
     game.instance.exports.init();
+
+    // Blobs are the settings and the like serialized by the module
+    // into its memory, that are kept in localStorage between the visits
+    const blobAddr = game.instance.exports.get_blob();
+    const blobCapacity = game.instance.exports.get_blob_capacity();
+    function loadBlob(key, load) {
+        try {
+            const saved = localStorage.getItem(key);
+            if (saved !== null) {
+                const bytes = JSON.parse(saved).slice(0, blobCapacity);
+                new Uint8Array(game.instance.exports.memory.buffer, blobAddr, blobCapacity).set(bytes);
+                load(bytes.length);
+            }
+        } catch (e) {
+            console.warn(`Could not load ${key}:`, e);
+        }
+    }
+    function saveBlob(key, save) {
+        const size = save();
+        const bytes = new Uint8Array(game.instance.exports.memory.buffer, blobAddr, size);
+        try {
+            localStorage.setItem(key, JSON.stringify(Array.from(bytes)));
+        } catch (e) {
+            console.warn(`Could not save ${key}:`, e);
+        }
+    }
+    loadBlob('config', game.instance.exports.load_config);
+    loadBlob('highScores', game.instance.exports.load_high_scores);
+
+    const params = new URLSearchParams(window.location.search);
+
+    // ?log=error|warn|info|debug
+    const logLevels = ['error', 'warn', 'info', 'debug'];
+    if (logLevels.includes(params.get('log'))) {
+        game.instance.exports.set_log_level(logLevels.indexOf(params.get('log')));
+    }
+
+    // ?seed=<number> replays the same game, ?daily gives everyone the
+    // same game for the whole day
+    if (params.has('daily')) {
+        const today = new Date();
+        game.instance.exports.set_seed(
+            today.getUTCFullYear() * 10000 + (today.getUTCMonth() + 1) * 100 + today.getUTCDate());
+    } else if (params.has('seed')) {
+        game.instance.exports.set_seed(Number(params.get('seed')));
+    }
+
     const displayAddr = game.instance.exports.get_display();
     const displayWidth = game.instance.exports.get_display_width();
     const displayHeight = game.instance.exports.get_display_height();
     const displaySize = displayWidth * displayHeight;
 
-    const gameCanvas = document.getElementById("game-canvas");
+    const audioAddr = game.instance.exports.get_audio_buffer();
+    const audioLen = game.instance.exports.get_audio_buffer_len();
+    const audioSampleRate = game.instance.exports.get_audio_sample_rate();
+    const audioRing = new Int16Array(game.instance.exports.memory.buffer, audioAddr, audioLen);
+    // Seconds of samples queued ahead to survive an occasional slow frame
+    const audioLatency = 0.05;
+    let audioCtx;
+    let audioReadPos = 0;
+    let audioTime = 0;
+
+    // Browsers only allow to start the sound after the user interacted
+    // with the page
+    function startAudio() {
+        if (audioCtx === undefined) {
+            audioCtx = new AudioContext();
+            audioReadPos = game.instance.exports.get_audio_position();
+        }
+    }
+
+    function playAudio() {
+        if (audioCtx === undefined) {
+            return;
+        }
+        const position = game.instance.exports.get_audio_position();
+        const count = (position - audioReadPos + audioLen) % audioLen;
+        if (count === 0) {
+            return;
+        }
+        const buffer = audioCtx.createBuffer(1, count, audioSampleRate);
+        const channel = buffer.getChannelData(0);
+        for (let i = 0; i < count; ++i) {
+            channel[i] = audioRing[(audioReadPos + i) % audioLen] / 32768;
+        }
+        audioReadPos = position;
+
+        const source = audioCtx.createBufferSource();
+        source.buffer = buffer;
+        source.connect(audioCtx.destination);
+        audioTime = Math.max(audioTime, audioCtx.currentTime + audioLatency);
+        source.start(audioTime);
+        audioTime += buffer.duration;
+    }
+
+    // Must match `enum Button` in game.rs
+    const Button = {Up: 0, Down: 1, Left: 2, Right: 3, Confirm: 4, Back: 5, Pause: 6};
+    const keyButtons = {
+        'ArrowUp': Button.Up, 'KeyW': Button.Up,
+        'ArrowDown': Button.Down, 'KeyS': Button.Down,
+        'ArrowLeft': Button.Left, 'KeyA': Button.Left,
+        'ArrowRight': Button.Right, 'KeyD': Button.Right,
+        'Enter': Button.Confirm,
+        'Escape': Button.Back, 'Backspace': Button.Back,
+        'Space': Button.Pause, 'KeyP': Button.Pause,
+    };
     document.addEventListener('keydown', e => {
-        console.log(e);
-        if (e.code === 'Space') {
-            game.instance.exports.toggle_pause();
+        startAudio();
+        if (e.repeat) {
+            return;
+        }
+        if (e.code in keyButtons) {
+            e.preventDefault();
+            game.instance.exports.key_down(keyButtons[e.code]);
+        } else if (e.code.startsWith('Digit')) {
+            game.instance.exports.select_weapon(Number(e.code.slice(5)) - 1);
+        }
+    });
+    document.addEventListener('keyup', e => {
+        if (e.code in keyButtons) {
+            game.instance.exports.key_up(keyButtons[e.code]);
         }
     });
+
+    // Gamepads can only be polled, so their buttons are turned into
+    // the key_down/key_up events by comparing with the previous frame
+    const stickThreshold = 0.5;
+    let gamepadButtons = {};
+    function pollGamepads() {
+        const held = {};
+        for (const gamepad of navigator.getGamepads ? navigator.getGamepads() : []) {
+            if (!gamepad) {
+                continue;
+            }
+            const pressed = index => gamepad.buttons[index] && gamepad.buttons[index].pressed;
+            const [stickX, stickY] = gamepad.axes;
+            if (pressed(12) || stickY < -stickThreshold) held[Button.Up] = true;
+            if (pressed(13) || stickY > stickThreshold) held[Button.Down] = true;
+            if (pressed(14) || stickX < -stickThreshold) held[Button.Left] = true;
+            if (pressed(15) || stickX > stickThreshold) held[Button.Right] = true;
+            if (pressed(0)) held[Button.Confirm] = true;
+            if (pressed(1)) held[Button.Back] = true;
+            if (pressed(9)) held[Button.Pause] = true;
+        }
+        for (const button of Object.values(Button)) {
+            if (held[button] && !gamepadButtons[button]) {
+                startAudio();
+                game.instance.exports.key_down(button);
+            } else if (!held[button] && gamepadButtons[button]) {
+                game.instance.exports.key_up(button);
+            }
+        }
+        gamepadButtons = held;
+    }
     gameCanvas.addEventListener('mousemove', e => {
         game.instance.exports.mouse_move(e.offsetX, e.offsetY);
     });
     gameCanvas.addEventListener('mousedown', e => {
-        game.instance.exports.mouse_click();
+        startAudio();
+        game.instance.exports.mouse_down();
+    });
+    document.addEventListener('mouseup', e => {
+        game.instance.exports.mouse_up();
     });
-
-    const ctx = gameCanvas.getContext('2d');
 
     let start;
     function step(timestamp) {
+        if (crashed) {
+            return;
+        }
         if (start === undefined) {
             start = timestamp;
         }
         const dt = (timestamp - start) * 0.001;
         start = timestamp;
 
+        pollGamepads();
         game.instance.exports.next_frame(dt);
+        if (game.instance.exports.poll_config_changed()) {
+            saveBlob('config', game.instance.exports.save_config);
+        }
+        if (game.instance.exports.poll_high_scores_changed()) {
+            saveBlob('highScores', game.instance.exports.save_high_scores);
+        }
+        playAudio();
         const frame = new ImageData(
             new Uint8ClampedArray(
                 memoryView.subarray(
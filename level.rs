@@ -0,0 +1,42 @@
+// Binary format of the scripted waves shared by the game and `levelc`.
+//
+// A level starts with MAGIC followed by WAVE_RECORD_SIZE byte records of:
+//   delay after the previous wave (in WAVE_DELAY_UNITs),
+//   index in ENEMY_KIND_NAMES,
+//   x position (0 is the left edge of the display, 255 is the right one),
+//   index in FORMATION_NAMES,
+//   amount of enemies in the formation,
+//   index in PATH_NAMES
+
+pub const MAGIC: [u8; 3] = [b'L', b'V', 1];
+pub const WAVE_RECORD_SIZE: usize = 6;
+pub const WAVE_DELAY_UNIT: f32 = 0.1;
+
+pub const ENEMY_KIND_NAMES: [&str; 7] = [
+    "basic",
+    "zigzag",
+    "diver",
+    "tank",
+    "splitter",
+    "splitling",
+    "boss",
+];
+
+pub const FORMATION_NAMES: [&str; 5] = [
+    "single",
+    "line",
+    "column",
+    "v",
+    "grid",
+];
+
+pub const PATH_NAMES: [&str; 8] = [
+    "default",
+    "straight",
+    "zigzag",
+    "chase",
+    "swoop-left",
+    "swoop-right",
+    "snake",
+    "orbit",
+];
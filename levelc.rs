@@ -0,0 +1,164 @@
+// Level compiler. Translates the human-readable description of the
+// scripted waves (see level.txt) into the binary format embedded into
+// the game (see level.rs).
+//
+// Usage: levelc <input.txt> <output.bin>
+
+mod level;
+
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::process::exit;
+
+struct Field<'a> {
+    text: &'a str,
+    col: usize,
+}
+
+// Splits the line into whitespace separated fields remembering their
+// columns. Everything after `#` is a comment.
+fn fields(line: &str) -> Vec<Field<'_>> {
+    let line = match line.find('#') {
+        Some(comment) => &line[..comment],
+        None => line,
+    };
+
+    let mut result = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                result.push(Field {
+                    text: &line[s..i],
+                    col: s + 1,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+fn parse_name(names: &[&str], field: &Field, what: &str) -> Result<u8, String> {
+    names
+        .iter()
+        .position(|name| *name == field.text)
+        .map(|index| index as u8)
+        .ok_or_else(|| format!("unknown {} `{}`, expected one of: {}", what, field.text, names.join(", ")))
+}
+
+// Parses seconds with at most one fractional digit into WAVE_DELAY_UNITs
+fn parse_delay(field: &Field) -> Result<u8, String> {
+    let error = || format!("invalid delay `{}`, expected seconds from 0.0 to {:.1} with at most one fractional digit",
+                           field.text, u8::MAX as f32 * level::WAVE_DELAY_UNIT);
+    let (whole, fraction) = match field.text.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (field.text, "0"),
+    };
+    if whole.is_empty() || fraction.len() != 1 || !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+        return Err(error());
+    }
+
+    let units = whole.parse::<u32>().map_err(|_| error())? * 10 + fraction.parse::<u32>().map_err(|_| error())?;
+    u8::try_from(units).map_err(|_| error())
+}
+
+// Parses percents of the display width into 0..=255
+fn parse_x(field: &Field) -> Result<u8, String> {
+    let error = || format!("invalid x `{}`, expected percents from 0% to 100%", field.text);
+    let percents = field
+        .text
+        .strip_suffix('%')
+        .and_then(|digits| digits.parse::<u32>().ok())
+        .filter(|percents| *percents <= 100)
+        .ok_or_else(error)?;
+    Ok(((percents * 255 + 50) / 100) as u8)
+}
+
+fn parse_count(field: &Field, formation: u8) -> Result<u8, String> {
+    let count = field
+        .text
+        .parse::<u8>()
+        .ok()
+        .filter(|count| *count > 0)
+        .ok_or_else(|| format!("invalid count `{}`, expected a number from 1 to 255", field.text))?;
+    if level::FORMATION_NAMES.get(formation as usize) == Some(&"single") && count != 1 {
+        return Err(format!("`single` formation expects count 1, but got {}", count));
+    }
+    Ok(count)
+}
+
+fn parse_wave(fields: &[Field]) -> Result<[u8; level::WAVE_RECORD_SIZE], (usize, String)> {
+    match fields {
+        [delay, kind, x, formation, count, path] => {
+            let formation_index = parse_name(&level::FORMATION_NAMES, formation, "formation").map_err(|e| (formation.col, e))?;
+            Ok([
+                parse_delay(delay).map_err(|e| (delay.col, e))?,
+                parse_name(&level::ENEMY_KIND_NAMES, kind, "enemy kind").map_err(|e| (kind.col, e))?,
+                parse_x(x).map_err(|e| (x.col, e))?,
+                formation_index,
+                parse_count(count, formation_index).map_err(|e| (count.col, e))?,
+                parse_name(&level::PATH_NAMES, path, "path").map_err(|e| (path.col, e))?,
+            ])
+        }
+        _ => {
+            let col = fields.get(level::WAVE_RECORD_SIZE).or(fields.last()).map(|field| field.col).unwrap_or(1);
+            Err((col, format!("expected {} fields `delay kind x formation count path`, but got {}",
+                              level::WAVE_RECORD_SIZE, fields.len())))
+        }
+    }
+}
+
+fn compile(path: &str, source: &str) -> Result<Vec<u8>, usize> {
+    let mut output = level::MAGIC.to_vec();
+    let mut errors = 0;
+    for (index, line) in source.lines().enumerate() {
+        let fields = fields(line);
+        if fields.is_empty() {
+            continue;
+        }
+
+        match parse_wave(&fields) {
+            Ok(record) => output.extend_from_slice(&record),
+            Err((col, message)) => {
+                eprintln!("{}:{}:{}: ERROR: {}", path, index + 1, col, message);
+                errors += 1;
+            }
+        }
+    }
+
+    if errors == 0 {
+        Ok(output)
+    } else {
+        Err(errors)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (input_path, output_path) = match args.as_slice() {
+        [_, input, output] => (input, output),
+        _ => {
+            eprintln!("Usage: levelc <input.txt> <output.bin>");
+            exit(1);
+        }
+    };
+
+    let source = fs::read_to_string(input_path).unwrap_or_else(|err| {
+        eprintln!("ERROR: could not read {}: {}", input_path, err);
+        exit(1);
+    });
+
+    let output = compile(input_path, &source).unwrap_or_else(|errors| {
+        eprintln!("ERROR: {} error(s) in {}", errors, input_path);
+        exit(1);
+    });
+
+    fs::write(output_path, &output).unwrap_or_else(|err| {
+        eprintln!("ERROR: could not write {}: {}", output_path, err);
+        exit(1);
+    });
+}
@@ -0,0 +1,83 @@
+// Software math so the game does not depend on the host for anything
+// but displaying the frames. Uses only the basic IEEE 754 operations,
+// so the results are bit-for-bit the same on every frontend.
+
+pub use core::f32::consts::PI;
+use core::f32::consts::{FRAC_PI_2, TAU};
+
+// TAU split into an exactly representable part with few significant
+// bits and the rest, so that k * TAU_HI stays exact during the range
+// reduction (Cody-Waite)
+const TAU_HI: f32 = 6.28125;
+const TAU_LO: f32 = 0.0019353072;
+
+// Valid for |x| < 2^31
+fn floor(x: f32) -> f32 {
+    let t = x as i32 as f32;
+    if t > x {
+        t - 1.0
+    } else {
+        t
+    }
+}
+
+// Reduces x to [-PI, PI]
+fn reduce(x: f32) -> f32 {
+    let k = floor(x / TAU + 0.5);
+    (x - k * TAU_HI) - k * TAU_LO
+}
+
+// Sine of x in [-3PI/2, 3PI/2]
+fn sin_kernel(x: f32) -> f32 {
+    // Fold into [-PI/2, PI/2] with sin(PI - x) = sin(x)
+    let x = if x > FRAC_PI_2 {
+        PI - x
+    } else if x < -FRAC_PI_2 {
+        -PI - x
+    } else {
+        x
+    };
+
+    // Taylor series up to x^11. The first dropped term is below 6e-8
+    // on [-PI/2, PI/2].
+    let x2 = x * x;
+    let y = x * (1.0 + x2 * (-1.0 / 6.0 + x2 * (1.0 / 120.0 + x2 * (-1.0 / 5040.0
+        + x2 * (1.0 / 362880.0 + x2 * (-1.0 / 39916800.0))))));
+    // Rounding must not push the result out of the range of sine
+    y.clamp(-1.0, 1.0)
+}
+
+// Absolute error is within 3.1e-7 for |x| <= 10000 and 1.2e-6 for
+// |x| <= 100000. The range reduction breaks down beyond that, so the
+// result is only guaranteed to be within [-1, 1] there. Returns NaN
+// for infinities and NaN.
+pub fn sin(x: f32) -> f32 {
+    if !x.is_finite() {
+        return f32::NAN;
+    }
+    sin_kernel(reduce(x))
+}
+
+// Same error bounds as sin
+pub fn cos(x: f32) -> f32 {
+    if !x.is_finite() {
+        return f32::NAN;
+    }
+    sin_kernel(reduce(x) + FRAC_PI_2)
+}
+
+// Relative error is within 1e-7 for all positive normal numbers.
+// Returns 0 for non-positive x.
+pub fn sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    // Halving the exponent bits gives a rough initial guess that a
+    // few Newton's iterations refine to the full f32 precision
+    let mut y = f32::from_bits((x.to_bits() >> 1) + 0x1FC0_0000);
+    for _ in 0..4 {
+        y = 0.5 * (y + x / y);
+    }
+    y
+}